@@ -24,7 +24,7 @@ fn find_attr_fields<'a>(fields: &'a Fields, name: &str) -> Option<&'a Ident> {
     }
 }
 
-#[proc_macro_derive(MIDIEvent, attributes(key, channel, playback))]
+#[proc_macro_derive(MIDIEvent, attributes(key, channel, playback, system_playback))]
 #[proc_macro_error]
 pub fn midi_event(input: TokenStream) -> TokenStream {
     let ast: DeriveInput = syn::parse(input).expect_or_abort("Couldn't parse for MIDIEvent");
@@ -39,6 +39,7 @@ pub fn midi_event(input: TokenStream) -> TokenStream {
         let channel_field = find_attr_fields(fields, "channel");
 
         let playback_event = has_attr(&ast.attrs, "playback");
+        let system_playback_event = has_attr(&ast.attrs, "system_playback");
 
         if key_field.is_some() && channel_field.is_none() {
             abort_call_site!(
@@ -50,18 +51,24 @@ pub fn midi_event(input: TokenStream) -> TokenStream {
         let mut generated_trait_impl = Vec::new();
         let mut generated_traits = Vec::new();
 
-        if playback_event {
+        if playback_event || system_playback_event {
+            let playback_trait = if system_playback_event {
+                quote! { SystemPlaybackEvent }
+            } else {
+                quote! { PlaybackEvent }
+            };
+
             generated_impl.push(quote! {
                 #[inline(always)]
                 pub fn as_u32(&self) -> u32 {
-                    PlaybackEvent::as_u32(self)
+                    #playback_trait::as_u32(self)
                 }
             });
 
             generated_trait_impl.push(quote! {
                 #[inline(always)]
                 fn as_u32(&self) -> Option<u32> {
-                    Some(PlaybackEvent::as_u32(self))
+                    Some(#playback_trait::as_u32(self))
                 }
             });
         } else {
@@ -23,6 +23,7 @@ fn chop_note(note: Note<u64>, chop_size: u64) -> impl Iterator<Item = Note<u64>>
                 channel: note.channel,
                 key: note.key,
                 velocity: note.velocity,
+                release_velocity: note.release_velocity,
                 len: end - start,
                 start,
             };
@@ -4,10 +4,7 @@ use midi_toolkit::{
     events::{Event, MIDIEventEnum},
     io::MIDIFile,
     pipe,
-    sequence::{
-        event::{cancel_tempo_events, scale_event_time},
-        unwrap_items, TimeCaster,
-    },
+    sequence::{event::to_seconds, unwrap_items},
 };
 
 pub fn main() {
@@ -18,12 +15,10 @@ pub fn main() {
     )
     .unwrap();
 
-    let ppq = midi.ppq();
+    let division = midi.time_division();
     let merged = pipe!(
         midi.iter_all_track_events_merged_batches()
-        |>TimeCaster::<f64>::cast_event_delta()
-        |>cancel_tempo_events(250000)
-        |>scale_event_time(1.0 / ppq as f64)
+        |>to_seconds(division)
         |>unwrap_items()
     );
 
@@ -9,21 +9,19 @@ use midi_toolkit::{
     io::MIDIFile,
     pipe,
     sequence::{
-        event::{cancel_tempo_events, merge_events_array, scale_event_time},
-        to_vec, unwrap_items, TimeCaster,
+        event::{merge_events_array, to_seconds},
+        to_vec, unwrap_items,
     },
 };
 
 fn main() {
     let midi = MIDIFile::open("D:/Midis/Forgiveness_REBORN_FINAL.mid", None).unwrap();
-    let ppq = midi.ppq();
+    let division = midi.time_division();
     let merged = pipe!(
         midi.iter_all_tracks()
         |>to_vec()
         |>merge_events_array()
-        |>TimeCaster::<f64>::cast_event_delta()
-        |>cancel_tempo_events(250000)
-        |>scale_event_time(1.0 / ppq as f64)
+        |>to_seconds(division)
         |>unwrap_items()
     );
 
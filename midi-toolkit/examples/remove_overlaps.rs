@@ -0,0 +1,51 @@
+use std::cell::Cell;
+
+use midi_toolkit::{
+    io::{MIDIFile, MIDIWriter},
+    pipe,
+    sequence::{
+        event::{filter_non_note_events, merge_events, merge_events_array},
+        events_to_notes,
+        note::{merge_notes_iterator, remove_overlaps, OverlapMode},
+        notes_to_events, to_vec, unwrap_items,
+    },
+};
+
+fn main() {
+    let file = MIDIFile::open("D:/Midis/tau2.5.9.mid", None).unwrap();
+
+    let writer = MIDIWriter::new("./out.mid", file.ppq()).unwrap();
+
+    let non_note_events = pipe!(
+        file.iter_all_tracks()
+        |>to_vec()
+        |>merge_events_array()
+        |>filter_non_note_events()
+    );
+
+    let notes_before = Cell::new(0u64);
+    let notes_after = Cell::new(0u64);
+
+    // Overlaps are removed across all the tracks, so the notes of every track are merged first
+    let notes = file
+        .iter_all_tracks()
+        .map(|track| pipe!(track|>events_to_notes()));
+    let notes =
+        pipe!(notes|>merge_notes_iterator()).inspect(|_| notes_before.set(notes_before.get() + 1));
+    let notes = pipe!(notes|>remove_overlaps(OverlapMode::Truncate))
+        .inspect(|_| notes_after.set(notes_after.get() + 1));
+    let note_events = pipe!(notes|>notes_to_events());
+
+    let merged = merge_events(note_events, non_note_events);
+
+    writer
+        .open_next_track()
+        .write_events_iter(pipe!(merged|>unwrap_items()))
+        .unwrap();
+
+    println!(
+        "Removed {} of {} notes",
+        notes_before.get() - notes_after.get(),
+        notes_before.get()
+    );
+}
@@ -98,10 +98,16 @@ pub trait PlaybackEvent: ChannelEvent {
     fn as_u32(&self) -> u32;
 }
 
+/// A trait that describes a system common or realtime event, which isn't connected to a channel
+/// but is always serializable to u32 for playback
+pub trait SystemPlaybackEvent {
+    fn as_u32(&self) -> u32;
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
-        events::{Event, MIDIEvent},
+        events::{Event, MIDIColor, MIDIEvent, SerializeEvent, TextEventKind},
         io::FullRamTrackReader,
         sequence::event::Delta,
     };
@@ -116,6 +122,15 @@ mod tests {
             vec![0xC0, 0x14],
             vec![0xD5, 0x7F],
             vec![0xE7, 0x23, 0x68],
+            vec![0xF1, 0x35],
+            vec![0xF2, 0x10, 0x02],
+            vec![0xF3, 0x05],
+            vec![0xF6],
+            vec![0xF8],
+            vec![0xFA],
+            vec![0xFB],
+            vec![0xFC],
+            vec![0xFE],
         ]
     }
 
@@ -141,4 +156,134 @@ mod tests {
             assert_eq!(serialized, compressed);
         }
     }
+
+    #[test]
+    fn serialize_round_trip() {
+        let mut events = make_example_playback_events();
+        events.push(vec![0xFF]);
+        for event_bytes in events.into_iter() {
+            // System messages are written as escapes, which are parsed back into the same event
+            let escaped = if event_bytes[0] >= 0xF0 {
+                [vec![0xF7, event_bytes.len() as u8], event_bytes.clone()].concat()
+            } else {
+                event_bytes.clone()
+            };
+
+            let event = parse_from_vec(escaped.clone());
+            let mut serialized = Vec::new();
+            event.serialize_event(&mut serialized).unwrap();
+            assert_eq!(serialized, escaped);
+
+            if event_bytes[0] != 0xFF {
+                assert_eq!(parse_from_vec(event_bytes), event);
+            }
+        }
+    }
+
+    #[test]
+    fn parse_system_events() {
+        assert_eq!(
+            parse_from_vec(vec![0xF1, 0x35]),
+            Event::new_delta_mtc_quarter_frame_event(64, 3, 5)
+        );
+        assert_eq!(
+            parse_from_vec(vec![0xF8]),
+            Event::new_delta_timing_clock_event(64)
+        );
+        assert_eq!(
+            parse_from_vec(vec![0xF7, 0x01, 0xFF]),
+            Event::new_delta_system_reset_event(64)
+        );
+        assert_eq!(parse_from_vec(vec![0xF7, 0x01, 0xFF]).as_u32(), Some(0xFF));
+        assert_eq!(
+            parse_from_vec(vec![0xF7, 0x03, 0xF2, 0x10, 0x02]),
+            Event::new_delta_song_position_pointer_event(64, 0x110)
+        );
+
+        // Escapes that aren't a single system message stay escapes
+        for data in [
+            vec![0xF8, 0xF8],
+            vec![0xF9],
+            vec![0xF3, 0x80],
+            vec![0x90, 0x40, 0x7F],
+        ] {
+            let mut bytes = vec![0xF7, data.len() as u8];
+            bytes.extend_from_slice(&data);
+            assert_eq!(
+                parse_from_vec(bytes),
+                Event::new_delta_escape_event(64, data)
+            );
+        }
+    }
+
+    #[test]
+    fn realtime_keeps_running_status() {
+        let bytes = vec![0x00, 0x90, 0x40, 0x7F, 0x00, 0xF8, 0x00, 0x41, 0x7F];
+        let reader = FullRamTrackReader::new_from_vec(None, bytes);
+        let events = crate::io::TrackParser::new(reader)
+            .map(|e| e.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![
+                Event::new_delta_note_on_event(0, 0, 0x40, 0x7F),
+                Event::new_delta_timing_clock_event(0),
+                Event::new_delta_note_on_event(0, 0, 0x41, 0x7F),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_color_events() {
+        let track_color = vec![
+            0xFF, 0x0A, 0x08, 0x00, 0x0F, 0x7F, 0x00, 0x10, 0x20, 0x30, 0xFF,
+        ];
+        let channel_gradient = vec![
+            0xFF, 0x0A, 0x0C, 0x00, 0x0F, 0x03, 0x00, 0x10, 0x20, 0x30, 0xFF, 0x40, 0x50, 0x60,
+            0x80,
+        ];
+
+        let event = parse_from_vec(track_color.clone());
+        assert_eq!(
+            event,
+            Event::new_delta_color_event(64, 0x7F, MIDIColor::new(0x10, 0x20, 0x30, 0xFF), None)
+        );
+        match &*event {
+            Event::Color(color) => assert!(color.is_all_channels()),
+            _ => unreachable!(),
+        }
+
+        let event = parse_from_vec(channel_gradient.clone());
+        assert_eq!(
+            event,
+            Event::new_delta_color_event(
+                64,
+                3,
+                MIDIColor::new(0x10, 0x20, 0x30, 0xFF),
+                Some(MIDIColor::new(0x40, 0x50, 0x60, 0x80))
+            )
+        );
+
+        for bytes in [track_color, channel_gradient] {
+            let mut serialized = Vec::new();
+            parse_from_vec(bytes.clone())
+                .serialize_event(&mut serialized)
+                .unwrap();
+            assert_eq!(serialized, bytes);
+        }
+    }
+
+    #[test]
+    fn non_color_0a_meta_stays_text() {
+        let bytes = vec![0xFF, 0x0A, 0x03, 0x61, 0x62, 0x63];
+        let event = parse_from_vec(bytes.clone());
+        assert_eq!(
+            event,
+            Event::new_delta_text_event(64, TextEventKind::Undefined, vec![0x61, 0x62, 0x63])
+        );
+
+        let mut serialized = Vec::new();
+        event.serialize_event(&mut serialized).unwrap();
+        assert_eq!(serialized, bytes);
+    }
 }
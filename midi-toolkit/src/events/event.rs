@@ -32,10 +32,29 @@ pub enum Event {
     #[playback]
     PitchWheelChange(Box<PitchWheelChangeEvent>),
     SystemExclusiveMessage(Box<SystemExclusiveMessageEvent>),
+    SystemExclusiveContinuation(Box<SystemExclusiveContinuationEvent>),
+    Escape(Box<EscapeEvent>),
     Undefined(Box<UndefinedEvent>),
+    #[playback]
+    MTCQuarterFrame(Box<MTCQuarterFrameEvent>),
+    #[playback]
     SongPositionPointer(Box<SongPositionPointerEvent>),
+    #[playback]
     SongSelect(Box<SongSelectEvent>),
+    #[playback]
     TuneRequest(Box<TuneRequestEvent>),
+    #[playback]
+    TimingClock(Box<TimingClockEvent>),
+    #[playback]
+    Start(Box<StartEvent>),
+    #[playback]
+    Continue(Box<ContinueEvent>),
+    #[playback]
+    Stop(Box<StopEvent>),
+    #[playback]
+    ActiveSensing(Box<ActiveSensingEvent>),
+    #[playback]
+    SystemReset(Box<SystemResetEvent>),
     EndOfExclusive(Box<EndOfExclusiveEvent>),
     TrackStart(Box<TrackStartEvent>),
     Text(Box<TextEvent>),
@@ -51,7 +70,31 @@ pub enum Event {
     KeySignature(Box<KeySignatureEvent>),
 }
 
-impl Event {}
+impl Event {
+    /// Maps a single complete system common or realtime message, like the data of an escape event,
+    /// to its event. Returns `None` for anything else.
+    pub(crate) fn from_system_message(message: &[u8]) -> Option<Event> {
+        if message.iter().skip(1).any(|b| *b > 0x7F) {
+            return None;
+        }
+        let event = match message {
+            [0xF1, data] => Event::new_mtc_quarter_frame_event((data >> 4) & 0x07, data & 0x0F),
+            [0xF2, lsb, msb] => {
+                Event::new_song_position_pointer_event(((*msb as u16) << 7) | *lsb as u16)
+            }
+            [0xF3, song] => Event::new_song_select_event(*song),
+            [0xF6] => Event::new_tune_request_event(),
+            [0xF8] => Event::new_timing_clock_event(),
+            [0xFA] => Event::new_start_event(),
+            [0xFB] => Event::new_continue_event(),
+            [0xFC] => Event::new_stop_event(),
+            [0xFE] => Event::new_active_sensing_event(),
+            [0xFF] => Event::new_system_reset_event(),
+            _ => return None,
+        };
+        Some(event)
+    }
+}
 
 impl BatchTempo for Event {
     fn inner_tempo(&self) -> Option<u32> {
@@ -3,7 +3,9 @@ use crate::io::MIDIWriteError;
 use crate::sequence::event::Delta;
 
 use super::event::Event;
-use super::{ChannelEvent, KeyEvent, MIDIEvent, MIDINum, PlaybackEvent, SerializeEvent};
+use super::{
+    ChannelEvent, KeyEvent, MIDIEvent, MIDINum, PlaybackEvent, SerializeEvent, SystemPlaybackEvent,
+};
 use derive::{MIDIEvent, NewEvent};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -14,6 +16,20 @@ pub struct MIDIColor {
     pub a: u8,
 }
 
+impl MIDIColor {
+    pub fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+
+    fn to_bytes(self) -> [u8; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Self::new(bytes[0], bytes[1], bytes[2], bytes[3])
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Copy)]
 pub enum TextEventKind {
     TextEvent = 1,
@@ -78,18 +94,20 @@ pub struct NoteOffEvent {
     pub channel: u8,
     #[key]
     pub key: u8,
+    /// The release velocity, usually 0 (or 64 if the device doesn't support release velocity)
+    pub velocity: u8,
 }
 
 impl SerializeEvent for NoteOffEvent {
     fn serialize_event<T: std::io::Write>(&self, buf: &mut T) -> Result<usize, MIDIWriteError> {
-        let event = [0x80 | self.channel, self.key, 0];
+        let event = [0x80 | self.channel, self.key, self.velocity];
         Ok(buf.write(&event)?)
     }
 }
 
 impl PlaybackEvent for NoteOffEvent {
     fn as_u32(&self) -> u32 {
-        (0x80 | self.channel as u32) | (self.key as u32) << 8
+        (0x80 | self.channel as u32) | (self.key as u32) << 8 | (self.velocity as u32) << 16
     }
 }
 
@@ -209,20 +227,73 @@ impl PlaybackEvent for PitchWheelChangeEvent {
     }
 }
 
+/// A system exclusive message, stored in a file as `F0 <length> <data>`.
+///
+/// `data` holds the bytes after the length exactly as they were in the file. A complete message
+/// ends with `F7`, otherwise the rest of the message follows in
+/// [`SystemExclusiveContinuationEvent`] packets. See [`join_sysex_packets`](crate::sequence::event::join_sysex_packets)
+/// to combine them.
 #[derive(Debug, MIDIEvent, Clone, NewEvent, PartialEq)]
 pub struct SystemExclusiveMessageEvent {
     pub data: Vec<u8>,
 }
 
+impl SystemExclusiveMessageEvent {
+    /// Whether the message is terminated with `F7`, or continues in the following packets.
+    pub fn is_complete(&self) -> bool {
+        self.data.last() == Some(&0xF7)
+    }
+}
+
+fn serialize_sysex_packet<T: std::io::Write>(
+    status: u8,
+    data: &[u8],
+    buf: &mut T,
+) -> Result<usize, MIDIWriteError> {
+    let mut vec = Vec::with_capacity(data.len() + 5);
+    vec.push(status);
+    vec.append(&mut encode_var_length_value(data.len() as u64));
+    vec.extend_from_slice(data);
+    Ok(buf.write(&vec)?)
+}
+
 impl SerializeEvent for SystemExclusiveMessageEvent {
     fn serialize_event<T: std::io::Write>(&self, buf: &mut T) -> Result<usize, MIDIWriteError> {
-        let mut vec = Vec::with_capacity(self.data.len() + 2);
-        vec.push(0xF0u8);
-        for v in self.data.iter() {
-            vec.push(*v);
-        }
-        vec.push(0xF7u8);
-        Ok(buf.write(&vec)?)
+        serialize_sysex_packet(0xF0, &self.data, buf)
+    }
+}
+
+/// A later packet of a system exclusive message that was split up, stored in a file as `F7 <length> <data>`.
+///
+/// The last packet of the message ends with `F7`.
+#[derive(Debug, MIDIEvent, Clone, NewEvent, PartialEq)]
+pub struct SystemExclusiveContinuationEvent {
+    pub data: Vec<u8>,
+}
+
+impl SystemExclusiveContinuationEvent {
+    /// Whether this is the last packet of the message.
+    pub fn is_complete(&self) -> bool {
+        self.data.last() == Some(&0xF7)
+    }
+}
+
+impl SerializeEvent for SystemExclusiveContinuationEvent {
+    fn serialize_event<T: std::io::Write>(&self, buf: &mut T) -> Result<usize, MIDIWriteError> {
+        serialize_sysex_packet(0xF7, &self.data, buf)
+    }
+}
+
+/// Arbitrary bytes to be sent as-is, such as realtime or system common messages,
+/// stored in a file as `F7 <length> <data>` outside of a system exclusive message.
+#[derive(Debug, MIDIEvent, Clone, NewEvent, PartialEq)]
+pub struct EscapeEvent {
+    pub data: Vec<u8>,
+}
+
+impl SerializeEvent for EscapeEvent {
+    fn serialize_event<T: std::io::Write>(&self, buf: &mut T) -> Result<usize, MIDIWriteError> {
+        serialize_sysex_packet(0xF7, &self.data, buf)
     }
 }
 
@@ -238,41 +309,199 @@ impl SerializeEvent for UndefinedEvent {
     }
 }
 
+/// Writes a system common or realtime message as an escape (`F7 <length> <message>`).
+///
+/// A MIDI file can only contain channel, sysex and meta events, and `FF` starts a meta event,
+/// so system messages can't be stored with their own status byte.
+fn serialize_escaped<T: std::io::Write>(
+    buf: &mut T,
+    message: &[u8],
+) -> Result<usize, MIDIWriteError> {
+    let header = [0xF7, message.len() as u8];
+    Ok(buf.write(&header)? + buf.write(message)?)
+}
+
+/// A MIDI time code quarter frame (`F1`), one eighth of a full SMPTE time code.
+#[derive(Debug, MIDIEvent, Clone, NewEvent, PartialEq)]
+#[system_playback]
+pub struct MTCQuarterFrameEvent {
+    /// Which part of the time code this frame holds, from 0 to 7
+    pub message_type: u8,
+    /// The 4 bit value of the time code part
+    pub value: u8,
+}
+
+impl MTCQuarterFrameEvent {
+    fn data(&self) -> u8 {
+        ((self.message_type & 0x07) << 4) | (self.value & 0x0F)
+    }
+}
+
+impl SerializeEvent for MTCQuarterFrameEvent {
+    fn serialize_event<T: std::io::Write>(&self, buf: &mut T) -> Result<usize, MIDIWriteError> {
+        serialize_escaped(buf, &[0xF1, self.data()])
+    }
+}
+
+impl SystemPlaybackEvent for MTCQuarterFrameEvent {
+    fn as_u32(&self) -> u32 {
+        0xF1 | (self.data() as u32) << 8
+    }
+}
+
 #[derive(Debug, MIDIEvent, Clone, NewEvent, PartialEq)]
+#[system_playback]
 pub struct SongPositionPointerEvent {
     pub position: u16,
 }
 
 impl SerializeEvent for SongPositionPointerEvent {
     fn serialize_event<T: std::io::Write>(&self, buf: &mut T) -> Result<usize, MIDIWriteError> {
-        let event = [
-            0xF2,
-            (self.position & 0x7F) as u8,
-            ((self.position >> 7) & 0x7F) as u8,
-        ];
-        Ok(buf.write(&event)?)
+        serialize_escaped(
+            buf,
+            &[
+                0xF2,
+                (self.position & 0x7F) as u8,
+                ((self.position >> 7) & 0x7F) as u8,
+            ],
+        )
+    }
+}
+
+impl SystemPlaybackEvent for SongPositionPointerEvent {
+    fn as_u32(&self) -> u32 {
+        0xF2 | ((self.position & 0x7F) as u32) << 8 | (((self.position >> 7) & 0x7F) as u32) << 16
     }
 }
 
 #[derive(Debug, MIDIEvent, Clone, NewEvent, PartialEq)]
+#[system_playback]
 pub struct SongSelectEvent {
     pub song: u8,
 }
 
 impl SerializeEvent for SongSelectEvent {
     fn serialize_event<T: std::io::Write>(&self, buf: &mut T) -> Result<usize, MIDIWriteError> {
-        let event = [0xF3, self.song];
-        Ok(buf.write(&event)?)
+        serialize_escaped(buf, &[0xF3, self.song])
+    }
+}
+
+impl SystemPlaybackEvent for SongSelectEvent {
+    fn as_u32(&self) -> u32 {
+        0xF3 | (self.song as u32) << 8
     }
 }
 
 #[derive(Debug, MIDIEvent, Clone, NewEvent, PartialEq)]
+#[system_playback]
 pub struct TuneRequestEvent {}
 
 impl SerializeEvent for TuneRequestEvent {
     fn serialize_event<T: std::io::Write>(&self, buf: &mut T) -> Result<usize, MIDIWriteError> {
-        let event = [0xF6];
-        Ok(buf.write(&event)?)
+        serialize_escaped(buf, &[0xF6])
+    }
+}
+
+impl SystemPlaybackEvent for TuneRequestEvent {
+    fn as_u32(&self) -> u32 {
+        0xF6
+    }
+}
+
+#[derive(Debug, MIDIEvent, Clone, NewEvent, PartialEq)]
+#[system_playback]
+pub struct TimingClockEvent {}
+
+impl SerializeEvent for TimingClockEvent {
+    fn serialize_event<T: std::io::Write>(&self, buf: &mut T) -> Result<usize, MIDIWriteError> {
+        serialize_escaped(buf, &[0xF8])
+    }
+}
+
+impl SystemPlaybackEvent for TimingClockEvent {
+    fn as_u32(&self) -> u32 {
+        0xF8
+    }
+}
+
+#[derive(Debug, MIDIEvent, Clone, NewEvent, PartialEq)]
+#[system_playback]
+pub struct StartEvent {}
+
+impl SerializeEvent for StartEvent {
+    fn serialize_event<T: std::io::Write>(&self, buf: &mut T) -> Result<usize, MIDIWriteError> {
+        serialize_escaped(buf, &[0xFA])
+    }
+}
+
+impl SystemPlaybackEvent for StartEvent {
+    fn as_u32(&self) -> u32 {
+        0xFA
+    }
+}
+
+#[derive(Debug, MIDIEvent, Clone, NewEvent, PartialEq)]
+#[system_playback]
+pub struct ContinueEvent {}
+
+impl SerializeEvent for ContinueEvent {
+    fn serialize_event<T: std::io::Write>(&self, buf: &mut T) -> Result<usize, MIDIWriteError> {
+        serialize_escaped(buf, &[0xFB])
+    }
+}
+
+impl SystemPlaybackEvent for ContinueEvent {
+    fn as_u32(&self) -> u32 {
+        0xFB
+    }
+}
+
+#[derive(Debug, MIDIEvent, Clone, NewEvent, PartialEq)]
+#[system_playback]
+pub struct StopEvent {}
+
+impl SerializeEvent for StopEvent {
+    fn serialize_event<T: std::io::Write>(&self, buf: &mut T) -> Result<usize, MIDIWriteError> {
+        serialize_escaped(buf, &[0xFC])
+    }
+}
+
+impl SystemPlaybackEvent for StopEvent {
+    fn as_u32(&self) -> u32 {
+        0xFC
+    }
+}
+
+#[derive(Debug, MIDIEvent, Clone, NewEvent, PartialEq)]
+#[system_playback]
+pub struct ActiveSensingEvent {}
+
+impl SerializeEvent for ActiveSensingEvent {
+    fn serialize_event<T: std::io::Write>(&self, buf: &mut T) -> Result<usize, MIDIWriteError> {
+        serialize_escaped(buf, &[0xFE])
+    }
+}
+
+impl SystemPlaybackEvent for ActiveSensingEvent {
+    fn as_u32(&self) -> u32 {
+        0xFE
+    }
+}
+
+/// A system reset (`FF`) message, stored as the escape `F7 01 FF` like the other system messages.
+#[derive(Debug, MIDIEvent, Clone, NewEvent, PartialEq)]
+#[system_playback]
+pub struct SystemResetEvent {}
+
+impl SerializeEvent for SystemResetEvent {
+    fn serialize_event<T: std::io::Write>(&self, buf: &mut T) -> Result<usize, MIDIWriteError> {
+        serialize_escaped(buf, &[0xFF])
+    }
+}
+
+impl SystemPlaybackEvent for SystemResetEvent {
+    fn as_u32(&self) -> u32 {
+        0xFF
     }
 }
 
@@ -334,16 +563,54 @@ impl SerializeEvent for UnknownMetaEvent {
     }
 }
 
+/// A track or channel color, stored in a file as the meta event
+/// `FF 0A <length> 00 0F <channel> 00 <r g b a> [<r g b a>]`.
+///
+/// The second color is optional, and makes the notes a gradient between the two colors.
 #[derive(Debug, MIDIEvent, Clone, NewEvent, PartialEq)]
 pub struct ColorEvent {
+    /// The channel the color applies to, or [`ColorEvent::ALL_CHANNELS`]
     pub channel: u8,
     pub col: MIDIColor,
     pub col2: Option<MIDIColor>,
 }
 
+impl ColorEvent {
+    /// The channel value that makes the color apply to all channels of the track.
+    pub const ALL_CHANNELS: u8 = 0x7F;
+
+    pub fn is_all_channels(&self) -> bool {
+        self.channel == Self::ALL_CHANNELS
+    }
+
+    /// Parses the data of a `0x0A` meta event, if it's in the color format.
+    pub(crate) fn from_meta_data(data: &[u8]) -> Option<ColorEvent> {
+        if (data.len() != 8 && data.len() != 12) || data[0..2] != [0x00, 0x0F] || data[3] != 0x00 {
+            return None;
+        }
+
+        Some(ColorEvent {
+            channel: data[2],
+            col: MIDIColor::from_bytes(&data[4..8]),
+            col2: if data.len() == 12 {
+                Some(MIDIColor::from_bytes(&data[8..12]))
+            } else {
+                None
+            },
+        })
+    }
+}
+
 impl SerializeEvent for ColorEvent {
-    fn serialize_event<T: std::io::Write>(&self, _buf: &mut T) -> Result<usize, MIDIWriteError> {
-        todo!();
+    fn serialize_event<T: std::io::Write>(&self, buf: &mut T) -> Result<usize, MIDIWriteError> {
+        let mut vec = Vec::with_capacity(15);
+        vec.extend_from_slice(&[0xFF, 0x0A, 0x08, 0x00, 0x0F, self.channel, 0x00]);
+        vec.extend_from_slice(&self.col.to_bytes());
+        if let Some(col2) = self.col2 {
+            vec[2] = 0x0C;
+            vec.extend_from_slice(&col2.to_bytes());
+        }
+        Ok(buf.write(&vec)?)
     }
 }
 
@@ -420,11 +687,41 @@ impl SerializeEvent for SMPTEOffsetEvent {
 #[derive(Debug, MIDIEvent, Clone, NewEvent, PartialEq)]
 pub struct TimeSignatureEvent {
     pub numerator: u8,
+    /// The denominator as a power of two exponent, e.g. `3` for x/8. Use [`real_denominator`](TimeSignatureEvent::real_denominator) for the actual value.
     pub denominator: u8,
+    /// The number of MIDI clocks (24 per quarter note) in a metronome click
     pub ticks_per_click: u8,
+    /// The number of notated 32nd notes in a MIDI quarter note (24 MIDI clocks), usually 8
     pub bb: u8,
 }
 
+impl TimeSignatureEvent {
+    /// The denominator of the time signature, e.g. `8` for 6/8.
+    pub fn real_denominator(&self) -> u32 {
+        1 << self.denominator.min(31)
+    }
+
+    /// The number of MIDI clocks (24 per quarter note) in a metronome click.
+    pub fn clocks_per_click(&self) -> u8 {
+        self.ticks_per_click
+    }
+
+    /// The number of notated 32nd notes in a MIDI quarter note.
+    pub fn thirty_seconds_per_quarter(&self) -> u8 {
+        self.bb
+    }
+
+    /// The length of a beat (one denominator unit) in ticks, rounded down.
+    pub fn ticks_per_beat(&self, ppq: u16) -> u64 {
+        (ppq as u64 * 4 / self.real_denominator() as u64).max(1)
+    }
+
+    /// The length of a bar in ticks.
+    pub fn ticks_per_bar(&self, ppq: u16) -> u64 {
+        self.ticks_per_beat(ppq) * self.numerator.max(1) as u64
+    }
+}
+
 impl SerializeEvent for TimeSignatureEvent {
     fn serialize_event<T: std::io::Write>(&self, buf: &mut T) -> Result<usize, MIDIWriteError> {
         let event = [
@@ -8,3 +8,9 @@ mod midi_writer;
 pub use midi_writer::*;
 mod track_parser;
 pub use track_parser::*;
+mod time_division;
+pub use time_division::*;
+mod chunk_repair;
+pub use chunk_repair::ChunkRepair;
+mod seek_index;
+pub use seek_index::*;
@@ -0,0 +1,135 @@
+use super::{errors::MIDILoadError, readers::MIDIReader};
+
+/// A correction that was made while loading a file with
+/// [`LoadOptions::repair_chunks`](crate::io::LoadOptions::repair_chunks) enabled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChunkRepair {
+    /// The length field of a track chunk didn't land on the next chunk or on the end of the file, so the
+    /// track boundaries were found from the end of track event and the start of the next chunk instead.
+    TrackLength {
+        track: u32,
+        /// The position of the track data, after the chunk header
+        position: u64,
+        declared_len: u32,
+        repaired_len: u32,
+    },
+    /// Bytes that weren't part of any track chunk were skipped.
+    SkippedBytes { position: u64, len: u64 },
+}
+
+const SCAN_BLOCK_SIZE: usize = 1 << 16;
+const END_OF_TRACK: [u8; 3] = [0xFF, 0x2F, 0x00];
+
+/// Scans `[from, to)` for windows of `width` bytes that match, returning the first or last match position.
+fn scan_with<T: MIDIReader>(
+    reader: &T,
+    from: u64,
+    to: u64,
+    width: usize,
+    find_last: bool,
+    mut is_match: impl FnMut(u64, &[u8]) -> Result<bool, MIDILoadError>,
+) -> Result<Option<u64>, MIDILoadError> {
+    let overlap = width as u64 - 1;
+    let mut found = None;
+    let mut pos = from;
+    while pos + overlap < to {
+        let count = (to - pos).min(SCAN_BLOCK_SIZE as u64) as usize;
+        let bytes = reader.read_bytes(pos, count)?;
+        for (i, window) in bytes.windows(width).enumerate() {
+            if is_match(pos + i as u64, window)? {
+                found = Some(pos + i as u64);
+                if !find_last {
+                    return Ok(found);
+                }
+            }
+        }
+        pos += count as u64 - overlap;
+    }
+    Ok(found)
+}
+
+/// Scans `[from, to)` for `pattern`, returning the first or last match position.
+fn scan_for<T: MIDIReader>(
+    reader: &T,
+    from: u64,
+    to: u64,
+    pattern: &[u8],
+    find_last: bool,
+) -> Result<Option<u64>, MIDILoadError> {
+    scan_with(reader, from, to, pattern.len(), find_last, |_, window| {
+        Ok(window == pattern)
+    })
+}
+
+/// Checks if a chunk header starts at `pos`, with a length that fits in the file. The length of
+/// an `MTrk` chunk isn't checked, as it might be the one that has to be repaired.
+pub(crate) fn is_chunk_header_at<T: MIDIReader>(
+    reader: &T,
+    pos: u64,
+) -> Result<bool, MIDILoadError> {
+    let remaining = reader.len() - pos;
+    if remaining < 8 {
+        return Ok(false);
+    }
+    let tag = reader.read_bytes(pos, 4)?;
+    Ok(is_chunk_tag(&tag) && (tag == b"MTrk" || 8 + chunk_len(reader, pos)? <= remaining))
+}
+
+/// Reads the length field of the chunk header at `pos`.
+fn chunk_len<T: MIDIReader>(reader: &T, pos: u64) -> Result<u64, MIDILoadError> {
+    let bytes = reader.read_bytes(pos + 4, 4)?;
+    Ok(bytes.iter().fold(0, |len, b| (len << 8) | *b as u64))
+}
+
+/// Finds the next chunk at or after `from`. That is an `MTrk` signature, or a chunk of another type
+/// that ends exactly on the end of the file or on the start of another chunk, so that bytes in the
+/// middle of a track that look like a chunk type aren't taken as one.
+pub(crate) fn find_next_chunk<T: MIDIReader>(
+    reader: &T,
+    from: u64,
+) -> Result<Option<u64>, MIDILoadError> {
+    scan_with(reader, from, reader.len(), 4, false, |pos, tag| {
+        if tag == b"MTrk" {
+            return Ok(true);
+        }
+        if !is_chunk_tag(tag) || !is_chunk_header_at(reader, pos)? {
+            return Ok(false);
+        }
+
+        let end = pos + 8 + chunk_len(reader, pos)?;
+        Ok(end == reader.len() || is_chunk_header_at(reader, end)?)
+    })
+}
+
+/// Checks if a track's declared length lands exactly on the end of the file or on the start of another chunk.
+/// The track doesn't have to end with an end of track event.
+pub(crate) fn is_track_len_valid<T: MIDIReader>(
+    reader: &T,
+    start: u64,
+    len: u32,
+) -> Result<bool, MIDILoadError> {
+    let end = start + len as u64;
+    if end > reader.len() {
+        return Ok(false);
+    }
+    Ok(end == reader.len() || is_chunk_header_at(reader, end)?)
+}
+
+/// Checks if the bytes look like a chunk type, which are made of 4 ASCII letters or digits.
+pub(crate) fn is_chunk_tag(tag: &[u8]) -> bool {
+    tag.len() == 4 && tag.iter().all(|b| b.is_ascii_alphanumeric())
+}
+
+/// Finds the real end of a track that starts at `start` and is followed by a chunk (or the end of the file)
+/// at `next`, by looking for the last end of track event in between.
+pub(crate) fn find_track_end<T: MIDIReader>(
+    reader: &T,
+    start: u64,
+    next: u64,
+) -> Result<u64, MIDILoadError> {
+    let end_of_track = scan_for(reader, start, next, &END_OF_TRACK, true)?;
+    Ok(match end_of_track {
+        Some(pos) => pos + END_OF_TRACK.len() as u64,
+        None => next,
+    })
+}
@@ -1,15 +1,73 @@
 use thiserror::Error;
 
-#[derive(Debug)]
+/// An error while opening a MIDI file and reading its chunks.
+#[derive(Debug, Error)]
 pub enum MIDILoadError {
-    CorruptChunks,
-    FilesystemError(std::io::Error),
-    FileTooBig,
+    /// A chunk didn't start with the expected tag
+    UnexpectedChunk {
+        position: u64,
+        expected: [u8; 4],
+        found: [u8; 4],
+    },
+    /// The MThd header chunk was too short to contain the format, track count and division
+    InvalidHeaderLength {
+        position: u64,
+        length: u32,
+    },
+    /// A read went past the end of the file, usually because a chunk length was wrong
+    UnexpectedEnd {
+        position: u64,
+        length: u64,
+        file_length: u64,
+    },
+    FilesystemError(#[from] std::io::Error),
+    /// The file was too big for the chosen reader
+    FileTooBig {
+        size: u64,
+        max_supported: u64,
+    },
+    /// The header has an SMPTE time division with a frame rate other than 24, 25, 29 or 30
+    InvalidTimeDivision {
+        position: u64,
+        value: u16,
+    },
 }
 
-impl From<std::io::Error> for MIDILoadError {
-    fn from(e: std::io::Error) -> Self {
-        MIDILoadError::FilesystemError(e)
+impl std::fmt::Display for MIDILoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MIDILoadError::UnexpectedChunk {
+                position,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Unexpected chunk (position: {position:#06x}, expected: \"{}\", found: \"{}\")",
+                expected.escape_ascii(),
+                found.escape_ascii()
+            ),
+            MIDILoadError::InvalidHeaderLength { position, length } => write!(
+                f,
+                "Invalid header length (position: {position:#06x}, length: {length})"
+            ),
+            MIDILoadError::UnexpectedEnd {
+                position,
+                length,
+                file_length,
+            } => write!(f, "Unexpected end of file (position: {position:#06x}, read length: {length}, file length: {file_length:#06x})"),
+            MIDILoadError::FilesystemError(e) => write!(f, "Filesystem error: {e}"),
+            MIDILoadError::FileTooBig {
+                size,
+                max_supported,
+            } => write!(
+                f,
+                "File too big (size: {size} bytes, max supported: {max_supported} bytes)"
+            ),
+            MIDILoadError::InvalidTimeDivision { position, value } => write!(
+                f,
+                "Invalid time division (position: {position:#06x}, value: {value:#06x})"
+            ),
+        }
     }
 }
 
@@ -26,6 +84,10 @@ pub enum MIDIParseError {
         found_track_end: u64,
     },
     FilesystemError(#[from] std::io::Error),
+    /// A [`SeekIndex`](crate::io::SeekIndex) doesn't belong to the file, or has checkpoints outside of their track
+    InvalidSeekIndex,
+    /// A [`ParserCheckpoint`](crate::io::ParserCheckpoint) doesn't point inside of the track it was used with
+    InvalidCheckpoint,
 }
 
 impl std::fmt::Display for MIDIParseError {
@@ -51,6 +113,87 @@ impl std::fmt::Display for MIDIParseError {
                 None => write!(f, "Unexpected track end (track start: {track_start:#06x}, expected end: {expected_track_end:#06x}, found end: {found_track_end:#06x})")
             },
             MIDIParseError::FilesystemError(e) => write!(f, "Filesystem error: {e}"),
+            MIDIParseError::InvalidSeekIndex => write!(f, "The seek index doesn't match the file"),
+            MIDIParseError::InvalidCheckpoint => {
+                write!(f, "The checkpoint doesn't belong to the track")
+            }
+        }
+    }
+}
+
+/// A problem that was recovered from while parsing a track in a lenient [`ParseOptions`](crate::io::ParseOptions) mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseWarning {
+    /// A data byte was found where a status byte was expected, and there was no running status to apply
+    SkippedByte {
+        track_number: Option<u32>,
+        position: u64,
+        byte: u8,
+    },
+    /// A data byte of a channel event was above 0x7F and was clamped into range
+    ClampedDataByte {
+        track_number: Option<u32>,
+        position: u64,
+        value: u8,
+    },
+    /// A meta event had an invalid length and was skipped
+    SkippedCorruptEvent {
+        track_number: Option<u32>,
+        position: u64,
+    },
+    /// The track ended in the middle of an event, the rest of the track was dropped
+    TruncatedTrack {
+        track_number: Option<u32>,
+        track_start: u64,
+        expected_track_end: u64,
+        found_track_end: u64,
+    },
+}
+
+impl std::fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let track = |track_number: &Option<u32>| match track_number {
+            Some(track_number) => format!("track {track_number}, "),
+            None => String::new(),
+        };
+
+        match self {
+            ParseWarning::SkippedByte {
+                track_number,
+                position,
+                byte,
+            } => write!(
+                f,
+                "Skipped stray byte {byte:#04x} ({}position: {position:#06x})",
+                track(track_number)
+            ),
+            ParseWarning::ClampedDataByte {
+                track_number,
+                position,
+                value,
+            } => write!(
+                f,
+                "Clamped out of range data byte {value:#04x} ({}position: {position:#06x})",
+                track(track_number)
+            ),
+            ParseWarning::SkippedCorruptEvent {
+                track_number,
+                position,
+            } => write!(
+                f,
+                "Skipped corrupt event ({}position: {position:#06x})",
+                track(track_number)
+            ),
+            ParseWarning::TruncatedTrack {
+                track_number,
+                track_start,
+                expected_track_end,
+                found_track_end,
+            } => write!(
+                f,
+                "Truncated track ({}track start: {track_start:#06x}, expected end: {expected_track_end:#06x}, found end: {found_track_end:#06x})",
+                track(track_number)
+            ),
         }
     }
 }
@@ -67,3 +210,21 @@ impl std::fmt::Display for MIDIWriteError {
         }
     }
 }
+
+/// A single error type for applications that both open MIDI files and iterate/write them.
+#[derive(Debug, Error)]
+pub enum MIDIError {
+    Load(#[from] MIDILoadError),
+    Parse(#[from] MIDIParseError),
+    Write(#[from] MIDIWriteError),
+}
+
+impl std::fmt::Display for MIDIError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MIDIError::Load(e) => write!(f, "Load error: {e}"),
+            MIDIError::Parse(e) => write!(f, "Parse error: {e}"),
+            MIDIError::Write(e) => write!(f, "Write error: {e}"),
+        }
+    }
+}
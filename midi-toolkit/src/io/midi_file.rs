@@ -1,4 +1,5 @@
 use std::{
+    convert::TryInto,
     fs::File,
     io::{Read, Seek},
     path::Path,
@@ -6,21 +7,34 @@ use std::{
 
 use crate::{
     events::Event,
+    gen_iter::GenIter,
+    notes::Note,
     sequence::{
         channels_into_threadpool,
         event::{
-            convert_events_into_batches, flatten_batches_to_events,
-            flatten_track_batches_to_events, into_track_events, merge_events_array, Delta,
-            EventBatch, Track,
+            chase_state_from, convert_events_into_batches, flatten_batches_to_events,
+            flatten_track_batches_to_events, into_track_events, into_track_notes,
+            merge_events_array, rescale_ppq, ChaseOptions, Delta, EventBatch, Rounding, TempoMap,
+            Track,
         },
+        events_to_notes,
+        note::grouped_multithreaded_merge_note_arrays,
     },
+    unwrap,
 };
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use std::fmt::Debug;
 
 use super::{
+    chunk_repair::{
+        find_next_chunk, find_track_end, is_chunk_header_at, is_chunk_tag, is_track_len_valid,
+        ChunkRepair,
+    },
     errors::{MIDILoadError, MIDIParseError},
-    readers::{DiskReader, MIDIReader, RAMReader},
-    track_parser::TrackParser,
+    readers::{DiskReader, MIDIReader, MmapReader, RAMReader, TrackReader},
+    seek_index::{IndexInterval, SeekIndex},
+    time_division::TimeDivision,
+    track_parser::{ParseOptions, ParserCheckpoint, TrackParser},
 };
 
 #[derive(Debug)]
@@ -29,18 +43,140 @@ struct TrackPos {
     len: u32,
 }
 
+impl TrackPos {
+    fn contains(&self, reader_pos: u64) -> bool {
+        reader_pos >= self.pos && reader_pos <= self.pos + self.len as u64
+    }
+}
+
+struct IndexedTrack {
+    checkpoints: Vec<ParserCheckpoint>,
+    tempos: Vec<(u64, u32)>,
+}
+
+/// Records the tempo events of a track, and its checkpoints for a [`SeekIndex`] if there is an interval.
+fn index_track<T: TrackReader>(
+    mut parser: TrackParser<T>,
+    interval: Option<IndexInterval>,
+) -> Result<IndexedTrack, MIDIParseError> {
+    let mut checkpoints = vec![parser.checkpoint()];
+    let mut tempos = Vec::new();
+
+    let mut events_since_checkpoint = 0;
+    let mut last_checkpoint_time = 0;
+    while let Some(event) = parser.next() {
+        if let Event::Tempo(tempo) = &*event? {
+            tempos.push((parser.time(), tempo.tempo));
+        }
+
+        events_since_checkpoint += 1;
+        let checkpoint_due = match interval {
+            Some(IndexInterval::Events(events)) => events_since_checkpoint >= events,
+            Some(IndexInterval::Ticks(ticks)) => parser.time() >= last_checkpoint_time + ticks,
+            None => false,
+        };
+        if checkpoint_due {
+            checkpoints.push(parser.checkpoint());
+            events_since_checkpoint = 0;
+            last_checkpoint_time = parser.time();
+        }
+    }
+
+    Ok(IndexedTrack {
+        checkpoints,
+        tempos,
+    })
+}
+
+/// Skips the events of a track that come before `tick`, and makes the first delta relative to `tick`.
+fn seek_track_events(
+    iter: impl Iterator<Item = Result<Delta<u64, Event>, MIDIParseError>>,
+    start_time: u64,
+    tick: u64,
+) -> impl Iterator<Item = Result<Delta<u64, Event>, MIDIParseError>> {
+    GenIter(
+        #[coroutine]
+        move || {
+            let mut time = start_time;
+            let mut prev_time = tick;
+            for e in iter {
+                let mut e = unwrap!(e);
+                time += e.delta;
+                if time < tick {
+                    continue;
+                }
+                e.delta = time - prev_time;
+                prev_time = time;
+                yield Ok(e);
+            }
+        },
+    )
+}
+
+/// Options for how a [`MIDIFile`] is loaded.
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    /// Instead of failing on track chunks with a wrong length, find the track boundaries from the
+    /// end of track events and the next `MTrk` signature. The corrections made are listed by
+    /// [`MIDIFile::chunk_repairs`].
+    pub repair_chunks: bool,
+}
+
+/// A chunk that isn't part of the standard MIDI file structure, such as the XF `XFIH`/`XFKM` chunks
+/// or vendor specific data.
+///
+/// Unknown chunks are skipped when a file is loaded, but kept in [`MIDIFile::extra_chunks`] so that
+/// they can be written back out with [`MIDIWriter::write_extra_chunk`](crate::io::MIDIWriter::write_extra_chunk).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtraChunk {
+    pub tag: [u8; 4],
+    pub data: Vec<u8>,
+    /// The number of track chunks that came before this chunk in the file
+    pub preceding_tracks: u32,
+}
+
 #[derive(Debug)]
 pub struct MIDIFile<T: MIDIReader> {
     reader: T,
     track_positions: Vec<TrackPos>,
+    chunk_repairs: Vec<ChunkRepair>,
+    extra_chunks: Vec<ExtraChunk>,
 
     format: u16,
-    ppq: u16,
+    division: TimeDivision,
+
+    parse_options: ParseOptions,
 }
 
 impl<T: 'static + MIDIReader> MIDIFile<T> {
+    /// Load a MIDI file from any [`MIDIReader`], with the given [`LoadOptions`].
+    ///
+    /// ## Example
+    ///```no_run
+    ///use std::fs::File;
+    ///use midi_toolkit::io::{DiskReader, LoadOptions, MIDIFile};
+    ///
+    ///let reader = DiskReader::new(File::open("damaged.mid").unwrap()).unwrap();
+    ///let options = LoadOptions {
+    ///    repair_chunks: true,
+    ///};
+    ///let file = MIDIFile::from_reader(reader, &options, None).unwrap();
+    ///
+    ///for repair in file.chunk_repairs() {
+    ///    println!("{repair:?}");
+    ///}
+    ///```
+    pub fn from_reader(
+        reader: T,
+        options: &LoadOptions,
+        read_progress: Option<&mut dyn FnMut(u32)>,
+    ) -> Result<Self, MIDILoadError> {
+        Self::new_from_disk_reader(reader, options, read_progress)
+    }
+
     fn new_from_disk_reader(
         reader: T,
+        options: &LoadOptions,
         mut read_progress: Option<&mut dyn FnMut(u32)>,
     ) -> Result<Self, MIDILoadError> {
         fn bytes_to_val(bytes: &[u8]) -> u32 {
@@ -64,12 +200,12 @@ impl<T: 'static + MIDIReader> MIDIFile<T> {
 
             let (header, len) = bytes.split_at(4);
 
-            let chars = text.as_bytes();
-
-            for i in 0..chars.len() {
-                if chars[i] != header[i] {
-                    return Err(MIDILoadError::CorruptChunks);
-                }
+            if header != text.as_bytes() {
+                return Err(MIDILoadError::UnexpectedChunk {
+                    position: pos,
+                    expected: text.as_bytes().try_into().unwrap(),
+                    found: header.try_into().unwrap(),
+                });
             }
 
             Ok(bytes_to_val(len))
@@ -78,30 +214,99 @@ impl<T: 'static + MIDIReader> MIDIFile<T> {
         let mut pos = 0u64;
 
         let header_len = read_header(&reader, pos, "MThd")?;
-        pos += 8;
-        if header_len != 6 {
-            return Err(MIDILoadError::CorruptChunks);
+        if header_len < 6 {
+            return Err(MIDILoadError::InvalidHeaderLength {
+                position: pos,
+                length: header_len,
+            });
         }
+        pos += 8;
 
-        let (format, ppq) = {
+        let (format, division) = {
             let header_data = reader.read_bytes(pos, 6)?;
-            pos += 6;
+            let division_position = pos + 4;
+            // Longer headers are allowed, the extra bytes are ignored
+            pos += header_len as u64;
             let (format_bytes, rest) = header_data.split_at(2);
-            let (_, ppq_bytes) = rest.split_at(2);
-            (
-                bytes_to_val(format_bytes) as u16,
-                bytes_to_val(ppq_bytes) as u16,
-            )
+            let (_, division_bytes) = rest.split_at(2);
+            let division_value = bytes_to_val(division_bytes) as u16;
+            let division = TimeDivision::from_u16(division_value);
+            if !division.is_valid() {
+                return Err(MIDILoadError::InvalidTimeDivision {
+                    position: division_position,
+                    value: division_value,
+                });
+            }
+            (bytes_to_val(format_bytes) as u16, division)
         };
 
         let mut track_count = 0;
         let mut track_positions = Vec::<TrackPos>::new();
+        let mut chunk_repairs = Vec::new();
+        let mut extra_chunks = Vec::new();
         while pos != reader.len() {
-            let len = read_header(&reader, pos, "MTrk")?;
+            if options.repair_chunks && !is_chunk_header_at(&reader, pos)? {
+                let next = find_next_chunk(&reader, pos)?.unwrap_or(reader.len());
+                chunk_repairs.push(ChunkRepair::SkippedBytes {
+                    position: pos,
+                    len: next - pos,
+                });
+                pos = next;
+                continue;
+            }
+
+            let header = reader.read_bytes(pos, 8)?;
+            let (tag, len) = header.split_at(4);
+            if !is_chunk_tag(tag) {
+                return Err(MIDILoadError::UnexpectedChunk {
+                    position: pos,
+                    expected: *b"MTrk",
+                    found: tag.try_into().unwrap(),
+                });
+            }
+            let mut len = bytes_to_val(len);
             pos += 8;
-            track_count += 1;
-            track_positions.push(TrackPos { len, pos });
-            pos += len as u64;
+
+            if tag != b"MTrk" {
+                let data = reader.read_bytes(pos, len as usize)?;
+                extra_chunks.push(ExtraChunk {
+                    tag: tag.try_into().unwrap(),
+                    data,
+                    preceding_tracks: track_count,
+                });
+                pos += len as u64;
+                continue;
+            }
+
+            if options.repair_chunks && !is_track_len_valid(&reader, pos, len)? {
+                let next = find_next_chunk(&reader, pos)?.unwrap_or(reader.len());
+                let end = find_track_end(&reader, pos, next)?;
+                let repaired_len = (end - pos) as u32;
+
+                if repaired_len != len {
+                    chunk_repairs.push(ChunkRepair::TrackLength {
+                        track: track_count,
+                        position: pos,
+                        declared_len: len,
+                        repaired_len,
+                    });
+                }
+                if end != next {
+                    chunk_repairs.push(ChunkRepair::SkippedBytes {
+                        position: end,
+                        len: next - end,
+                    });
+                }
+
+                len = repaired_len;
+                track_positions.push(TrackPos { len, pos });
+                track_count += 1;
+                pos = next;
+            } else {
+                track_count += 1;
+                track_positions.push(TrackPos { len, pos });
+                pos += len as u64;
+            }
 
             if let Some(progress) = read_progress.as_mut().take() {
                 progress(track_count);
@@ -111,9 +316,12 @@ impl<T: 'static + MIDIReader> MIDIFile<T> {
         track_positions.shrink_to_fit();
         Ok(MIDIFile {
             reader,
-            ppq,
+            division,
             format,
             track_positions,
+            chunk_repairs,
+            extra_chunks,
+            parse_options: ParseOptions::default(),
         })
     }
 
@@ -133,6 +341,20 @@ impl<T: 'static + MIDIReader> MIDIFile<T> {
         tracks.into_iter()
     }
 
+    /// Iterate all tracks converted to a different ppq with [`rescale_ppq`], e.g. for writing them
+    /// into a new file. The tracks are rescaled from the same absolute ticks, so they stay aligned.
+    ///
+    /// For SMPTE files, the equivalent [`ppq`](#method.ppq) is used as the source.
+    pub fn iter_all_tracks_rescaled(
+        &self,
+        ppq: u16,
+        rounding: Rounding,
+    ) -> impl Iterator<Item = impl Iterator<Item = Result<Delta<u64, Event>, MIDIParseError>>> {
+        let from = self.ppq();
+        self.iter_all_tracks()
+            .map(move |track| rescale_ppq(track, from, ppq, rounding))
+    }
+
     pub fn iter_all_events_merged(
         &self,
     ) -> impl Iterator<Item = Result<Delta<u64, Event>, MIDIParseError>> {
@@ -171,16 +393,274 @@ impl<T: 'static + MIDIReader> MIDIFile<T> {
         merge_events_array(batched_tracks_threaded)
     }
 
+    /// Iterate the notes of all tracks, merged into one sequence sorted by start.
+    ///
+    /// The notes of each track are extracted on worker threads with [`events_to_notes`],
+    /// and merged on worker threads too.
+    pub fn iter_all_notes_merged(&self) -> impl Iterator<Item = Result<Note<u64>, MIDIParseError>> {
+        let note_tracks = self.iter_all_tracks().map(events_to_notes).collect();
+        let note_tracks_threaded = channels_into_threadpool(note_tracks, 10);
+        grouped_multithreaded_merge_note_arrays(note_tracks_threaded)
+    }
+
+    /// Same as [`iter_all_notes_merged`](#method.iter_all_notes_merged), with the track index of each note.
+    pub fn iter_all_track_notes_merged(
+        &self,
+    ) -> impl Iterator<Item = Result<Track<Note<u64>>, MIDIParseError>> {
+        let note_tracks = self
+            .iter_all_tracks()
+            .map(events_to_notes)
+            .enumerate()
+            .map(|(i, track)| into_track_notes(track, i as u32))
+            .collect();
+        let note_tracks_threaded = channels_into_threadpool(note_tracks, 10);
+        grouped_multithreaded_merge_note_arrays(note_tracks_threaded)
+    }
+
     pub fn iter_track(
         &self,
         track: u32,
     ) -> impl Iterator<Item = Result<Delta<u64, Event>, MIDIParseError>> {
+        self.track_parser(track)
+    }
+
+    /// Creates the parser of a track, which unlike [`iter_track`](#method.iter_track) can be used
+    /// to create [`ParserCheckpoint`]s while iterating.
+    pub fn track_parser(&self, track: u32) -> TrackParser<T::ByteReader> {
         let reader = self.open_track_reader(track);
-        TrackParser::new(reader)
+        TrackParser::new_with_options(reader, self.parse_options.clone())
     }
 
+    /// Resume iterating a track from a checkpoint that was created by the parser of the same track.
+    ///
+    /// Returns [`MIDIParseError::InvalidCheckpoint`] if the track doesn't exist or the checkpoint is outside of it.
+    ///
+    /// ## Example
+    ///```no_run
+    ///use midi_toolkit::io::MIDIFile;
+    ///
+    ///let file = MIDIFile::open_in_ram("song.mid", None).unwrap();
+    ///
+    ///let mut parser = file.track_parser(0);
+    ///parser.nth(1000);
+    ///let checkpoint = parser.checkpoint();
+    ///
+    ///// Later, continue from event 1001 without parsing the first 1000 events again
+    ///for event in file.iter_track_from_checkpoint(0, checkpoint).unwrap() {
+    ///    println!("{:?}", event.unwrap());
+    ///}
+    ///```
+    pub fn iter_track_from_checkpoint(
+        &self,
+        track: u32,
+        checkpoint: ParserCheckpoint,
+    ) -> Result<impl Iterator<Item = Result<Delta<u64, Event>, MIDIParseError>>, MIDIParseError>
+    {
+        match self.track_positions.get(track as usize) {
+            Some(pos) if pos.contains(checkpoint.reader_pos()) => {
+                Ok(self.track_parser_from_checkpoint(track, checkpoint))
+            }
+            _ => Err(MIDIParseError::InvalidCheckpoint),
+        }
+    }
+
+    /// Same as [`iter_track_from_checkpoint`](#method.iter_track_from_checkpoint), for a checkpoint that was already checked.
+    fn track_parser_from_checkpoint(
+        &self,
+        track: u32,
+        checkpoint: ParserCheckpoint,
+    ) -> TrackParser<T::ByteReader> {
+        let pos = &self.track_positions[track as usize];
+        let reader = self.reader.open_reader_at(
+            Some(track),
+            pos.pos,
+            pos.len as u64,
+            checkpoint.reader_pos(),
+        );
+        TrackParser::from_checkpoint_with_options(reader, checkpoint, self.parse_options.clone())
+    }
+
+    /// Parses every track once to build a [`SeekIndex`], for starting iteration at any tick or time
+    /// with [`iter_all_events_merged_from`](#method.iter_all_events_merged_from).
+    ///
+    /// **NOTE:** The tracks are parsed on the `rayon` threadpool.
+    pub fn build_seek_index(&self, interval: IndexInterval) -> Result<SeekIndex, MIDIParseError> {
+        let (tracks, tempo_map) = self.index_tracks(Some(interval))?;
+        Ok(SeekIndex {
+            file_len: self.reader.len(),
+            tracks,
+            tempo_map,
+        })
+    }
+
+    /// Parses every track once to build a [`TempoMap`] of the file, for converting between ticks and seconds.
+    ///
+    /// **NOTE:** The tracks are parsed on the `rayon` threadpool. If a [`SeekIndex`] was already built,
+    /// [`SeekIndex::tempo_map`] is faster.
+    pub fn tempo_map(&self) -> Result<TempoMap<u64>, MIDIParseError> {
+        let (_, tempo_map) = self.index_tracks(None)?;
+        Ok(tempo_map)
+    }
+
+    /// Parses every track on the `rayon` threadpool, collecting the checkpoints of each track and the tempo map of the file.
+    fn index_tracks(
+        &self,
+        interval: Option<IndexInterval>,
+    ) -> Result<(Vec<Vec<ParserCheckpoint>>, TempoMap<u64>), MIDIParseError> {
+        let parsers = (0..self.track_count())
+            .map(|i| self.track_parser(i as u32))
+            .collect::<Vec<_>>();
+        let indexed = parsers
+            .into_par_iter()
+            .map(|parser| index_track(parser, interval))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut tracks = Vec::with_capacity(indexed.len());
+        let mut tempos = Vec::new();
+        for track in indexed {
+            tracks.push(track.checkpoints);
+            tempos.extend(track.tempos);
+        }
+        tempos.sort_by_key(|(tick, _)| *tick);
+
+        Ok((
+            tracks,
+            TempoMap::from_absolute_tempos(tempos, self.division),
+        ))
+    }
+
+    /// Checks that a seek index, for example one loaded from a sidecar file, belongs to this file,
+    /// and that all of its checkpoints are inside their tracks.
+    pub fn is_seek_index_valid(&self, index: &SeekIndex) -> bool {
+        index.file_len == self.reader.len()
+            && index.tempo_map.division() == self.division
+            && index.track_count() == self.track_count()
+            && index
+                .tracks
+                .iter()
+                .zip(self.track_positions.iter())
+                .all(|(checkpoints, pos)| checkpoints.iter().all(|c| pos.contains(c.reader_pos)))
+    }
+
+    /// Iterate a track starting at a tick, using the closest checkpoint before it in the index.
+    ///
+    /// The delta of the first event is relative to `tick`.
+    /// Returns [`MIDIParseError::InvalidSeekIndex`] if the index doesn't belong to this file.
+    pub fn iter_track_from(
+        &self,
+        index: &SeekIndex,
+        track: u32,
+        tick: u64,
+    ) -> Result<impl Iterator<Item = Result<Delta<u64, Event>, MIDIParseError>>, MIDIParseError>
+    {
+        if !self.is_seek_index_valid(index) {
+            return Err(MIDIParseError::InvalidSeekIndex);
+        }
+        Ok(self.seek_track(index, track, tick))
+    }
+
+    /// Same as [`iter_track_from`](#method.iter_track_from), for an index that was already checked.
+    fn seek_track(
+        &self,
+        index: &SeekIndex,
+        track: u32,
+        tick: u64,
+    ) -> impl Iterator<Item = Result<Delta<u64, Event>, MIDIParseError>> {
+        let checkpoint = index.checkpoint_before(track, tick).clone();
+        let start_time = checkpoint.time();
+        seek_track_events(
+            self.track_parser_from_checkpoint(track, checkpoint),
+            start_time,
+            tick,
+        )
+    }
+
+    /// Similar to [`iter_all_events_merged`](#method.iter_all_events_merged), except starts at a tick
+    /// without parsing the whole file up to it.
+    ///
+    /// The delta of the first event is relative to `tick`. Events before the tick (including tempo events)
+    /// are skipped, use [`SeekIndex::tempo_at`] for the tempo at the start.
+    pub fn iter_all_events_merged_from(
+        &self,
+        index: &SeekIndex,
+        tick: u64,
+    ) -> Result<impl Iterator<Item = Result<Delta<u64, Event>, MIDIParseError>>, MIDIParseError>
+    {
+        if !self.is_seek_index_valid(index) {
+            return Err(MIDIParseError::InvalidSeekIndex);
+        }
+        let batched_tracks = (0..self.track_count())
+            .map(|i| convert_events_into_batches(self.seek_track(index, i as u32, tick)))
+            .collect();
+        let batched_tracks_threaded = channels_into_threadpool(batched_tracks, 10);
+        Ok(flatten_batches_to_events(merge_events_array(
+            batched_tracks_threaded,
+        )))
+    }
+
+    /// Similar to [`iter_all_events_merged`](#method.iter_all_events_merged), except starts at a tick,
+    /// with the events before it replaced by events that restore the state at the tick.
+    /// See [`chase_state_from`] for details.
+    ///
+    /// The whole file up to the tick still has to be parsed to find the state.
+    pub fn iter_all_events_merged_chased_from(
+        &self,
+        tick: u64,
+        options: ChaseOptions,
+    ) -> impl Iterator<Item = Result<Delta<u64, Event>, MIDIParseError>> {
+        chase_state_from(self.iter_all_events_merged(), tick, options)
+    }
+
+    /// Similar to [`iter_all_events_merged_from`](#method.iter_all_events_merged_from), except starts at
+    /// a time in seconds, which is converted to ticks with the tempo events of the file.
+    pub fn iter_all_events_merged_from_seconds(
+        &self,
+        index: &SeekIndex,
+        seconds: f64,
+    ) -> Result<impl Iterator<Item = Result<Delta<u64, Event>, MIDIParseError>>, MIDIParseError>
+    {
+        self.iter_all_events_merged_from(index, index.seconds_to_ticks(seconds))
+    }
+
+    /// The options used to parse every track iterated from this file.
+    pub fn parse_options(&self) -> &ParseOptions {
+        &self.parse_options
+    }
+
+    /// Set the options used to parse every track iterated from this file,
+    /// for example [`ParseOptions::lenient`] to play damaged files to the end.
+    pub fn set_parse_options(&mut self, options: ParseOptions) {
+        self.parse_options = options;
+    }
+
+    /// The ticks per quarter note of the file.
+    ///
+    /// SMPTE files don't have a ppq, so the equivalent ppq at the default tempo (120 bpm)
+    /// is returned instead. Use [`time_division`](#method.time_division) for exact timing.
     pub fn ppq(&self) -> u16 {
-        self.ppq
+        match self.division {
+            TimeDivision::TicksPerQuarter(ppq) => ppq,
+            TimeDivision::Smpte {
+                fps,
+                ticks_per_frame,
+            } => (fps as u16 * ticks_per_frame as u16 / 2).max(1),
+        }
+    }
+
+    pub fn time_division(&self) -> TimeDivision {
+        self.division
+    }
+
+    /// The corrections made to the file's chunks while loading it.
+    ///
+    /// Always empty unless [`LoadOptions::repair_chunks`] was enabled.
+    pub fn chunk_repairs(&self) -> &[ChunkRepair] {
+        &self.chunk_repairs
+    }
+
+    /// The chunks with an unknown tag that were skipped while loading the file, in file order.
+    pub fn extra_chunks(&self) -> &[ExtraChunk] {
+        &self.extra_chunks
     }
 
     pub fn format(&self) -> u16 {
@@ -200,7 +680,7 @@ impl MIDIFile<DiskReader> {
         let reader = File::open(filename)?;
         let reader = DiskReader::new(reader)?;
 
-        MIDIFile::new_from_disk_reader(reader, read_progress)
+        MIDIFile::new_from_disk_reader(reader, &LoadOptions::default(), read_progress)
     }
 
     pub fn open_from_stream<T: 'static + Read + Seek + Send>(
@@ -209,7 +689,26 @@ impl MIDIFile<DiskReader> {
     ) -> Result<Self, MIDILoadError> {
         let reader = DiskReader::new(stream)?;
 
-        MIDIFile::new_from_disk_reader(reader, read_progress)
+        MIDIFile::new_from_disk_reader(reader, &LoadOptions::default(), read_progress)
+    }
+}
+
+impl MIDIFile<MmapReader> {
+    /// Open a MIDI file through a read-only memory map.
+    ///
+    /// Parses as fast as [`open_in_ram`](MIDIFile::open_in_ram) without copying the file into memory,
+    /// which makes it the best choice for multi-gigabyte files.
+    ///
+    /// The file must not be modified or truncated by another process while it is open.
+    pub fn open_mmap(
+        filename: impl AsRef<Path>,
+        read_progress: Option<&mut dyn FnMut(u32)>,
+    ) -> Result<Self, MIDILoadError> {
+        let file = File::open(filename)?;
+        // Safety: The map is read-only, and the caller is told to not modify the file while it is open
+        let reader = unsafe { MmapReader::new(&file)? };
+
+        MIDIFile::new_from_disk_reader(reader, &LoadOptions::default(), read_progress)
     }
 }
 
@@ -221,7 +720,7 @@ impl MIDIFile<RAMReader> {
         let reader = File::open(filename)?;
         let reader = RAMReader::new(reader)?;
 
-        MIDIFile::new_from_disk_reader(reader, read_progress)
+        MIDIFile::new_from_disk_reader(reader, &LoadOptions::default(), read_progress)
     }
 
     pub fn open_from_stream_in_ram<T: 'static + Read + Seek + Send>(
@@ -230,6 +729,682 @@ impl MIDIFile<RAMReader> {
     ) -> Result<Self, MIDILoadError> {
         let reader = RAMReader::new(stream)?;
 
-        MIDIFile::new_from_disk_reader(reader, read_progress)
+        MIDIFile::new_from_disk_reader(reader, &LoadOptions::default(), read_progress)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::{
+        events::{Event, SerializeEventWithDelta},
+        io::{
+            ChunkRepair, ExtraChunk, IndexInterval, LoadOptions, MIDIError, MIDIFile,
+            MIDILoadError, MIDIParseError, MIDIWriter, MmapReader, ParseOptions, RAMReader,
+            SeekIndex, TimeDivision,
+        },
+        notes::MIDINote,
+        sequence::{event::Rounding, to_vec_result, unwrap_items},
+    };
+
+    fn make_midi_bytes(division: [u8; 2]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"MThd");
+        bytes.extend_from_slice(&[0, 0, 0, 6, 0, 1, 0, 1]);
+        bytes.extend_from_slice(&division);
+        bytes.extend_from_slice(b"MTrk");
+        bytes.extend_from_slice(&[0, 0, 0, 4, 0x00, 0xFF, 0x2F, 0x00]);
+        bytes
+    }
+
+    #[test]
+    fn read_smpte_division() {
+        let bytes = make_midi_bytes([0xE7, 0x28]);
+        let file = MIDIFile::open_from_stream_in_ram(Cursor::new(bytes), None).unwrap();
+
+        assert_eq!(
+            file.time_division(),
+            TimeDivision::Smpte {
+                fps: 25,
+                ticks_per_frame: 40
+            }
+        );
+        assert_eq!(file.ppq(), 500);
+    }
+
+    #[test]
+    fn invalid_smpte_division_errors() {
+        let bytes = make_midi_bytes([0x80, 0x28]);
+        let result = MIDIFile::open_from_stream_in_ram(Cursor::new(bytes), None);
+
+        assert!(matches!(
+            result,
+            Err(MIDILoadError::InvalidTimeDivision {
+                position: 12,
+                value: 0x8028
+            })
+        ));
+    }
+
+    #[test]
+    fn read_ppq_division() {
+        let bytes = make_midi_bytes([0x03, 0xC0]);
+        let file = MIDIFile::open_from_stream_in_ram(Cursor::new(bytes), None).unwrap();
+
+        assert_eq!(file.time_division(), TimeDivision::TicksPerQuarter(960));
+        assert_eq!(file.ppq(), 960);
+    }
+
+    #[test]
+    fn write_smpte_division() {
+        let path = std::env::temp_dir().join("midi_toolkit_write_smpte_division.mid");
+        let division = TimeDivision::Smpte {
+            fps: 29,
+            ticks_per_frame: 80,
+        };
+
+        {
+            let mut writer = MIDIWriter::new(path.to_str().unwrap(), 96).unwrap();
+            writer.write_ppq(division).unwrap();
+            writer.open_next_track().end().unwrap();
+            writer.end().unwrap();
+        }
+
+        let file = MIDIFile::open_in_ram(&path, None).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(file.time_division(), division);
+    }
+
+    #[test]
+    fn lenient_merged_iteration_of_truncated_track() {
+        let mut bytes = make_midi_bytes([0x00, 0x60]);
+        bytes[11] = 2;
+        bytes.extend_from_slice(b"MTrk");
+        bytes.extend_from_slice(&[0, 0, 0, 6, 0x10, 0x90, 0x40, 0x7F, 0x10, 0x80]);
+
+        let mut file = MIDIFile::open_from_stream_in_ram(Cursor::new(bytes), None).unwrap();
+        assert!(to_vec_result(file.iter_all_events_merged()).is_err());
+
+        file.set_parse_options(ParseOptions::lenient());
+        let events: Vec<_> = unwrap_items(file.iter_all_events_merged()).collect();
+        assert_eq!(
+            events,
+            vec![Event::new_delta_note_on_event(0x10, 0, 0x40, 0x7F)]
+        );
+    }
+
+    fn make_damaged_midi_bytes() -> Vec<u8> {
+        let mut bytes = make_midi_bytes([0x00, 0x60]);
+        // Declare the first track as much longer than it is
+        bytes[21] = 0x40;
+        // Garbage between the two tracks
+        bytes.extend_from_slice(&[0x12, 0x34]);
+        bytes.extend_from_slice(b"MTrk");
+        bytes.extend_from_slice(&[0, 0, 0, 2, 0x10, 0x90, 0x40, 0x7F, 0x10, 0x80, 0x40, 0x00]);
+        bytes.extend_from_slice(&[0x00, 0xFF, 0x2F, 0x00]);
+        bytes
+    }
+
+    #[test]
+    fn wrong_track_length_fails_by_default() {
+        let bytes = make_damaged_midi_bytes();
+        assert!(MIDIFile::open_from_stream_in_ram(Cursor::new(bytes), None).is_err());
+    }
+
+    #[test]
+    fn repair_wrong_track_lengths() {
+        let bytes = make_damaged_midi_bytes();
+        let reader = RAMReader::new(Cursor::new(bytes)).unwrap();
+        let options = LoadOptions {
+            repair_chunks: true,
+        };
+        let file = MIDIFile::from_reader(reader, &options, None).unwrap();
+
+        assert_eq!(file.track_count(), 2);
+        assert_eq!(
+            file.chunk_repairs(),
+            &[
+                ChunkRepair::TrackLength {
+                    track: 0,
+                    position: 22,
+                    declared_len: 0x40,
+                    repaired_len: 4,
+                },
+                ChunkRepair::SkippedBytes {
+                    position: 26,
+                    len: 2,
+                },
+                ChunkRepair::TrackLength {
+                    track: 1,
+                    position: 36,
+                    declared_len: 2,
+                    repaired_len: 12,
+                },
+            ]
+        );
+
+        let events = to_vec_result(file.iter_all_events_merged()).unwrap();
+        assert_eq!(
+            events,
+            vec![
+                Event::new_delta_note_on_event(0x10, 0, 0x40, 0x7F),
+                Event::new_delta_note_off_event(0x10, 0, 0x40, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn repair_track_without_end_before_extra_chunk() {
+        let track = [0x00, 0x90, 0x40, 0x7F, 0x10, 0x80, 0x40, 0x00];
+        let make_bytes = |declared_len: u8| {
+            let mut bytes = Vec::new();
+            bytes.extend_from_slice(b"MThd");
+            bytes.extend_from_slice(&[0, 0, 0, 6, 0, 1, 0, 1, 0x01, 0xE0]);
+            bytes.extend_from_slice(b"MTrk");
+            bytes.extend_from_slice(&[0, 0, 0, declared_len]);
+            bytes.extend_from_slice(&track);
+            bytes.extend_from_slice(b"XFIH");
+            bytes.extend_from_slice(&[0, 0, 0, 3, 1, 2, 3]);
+            bytes
+        };
+        let options = LoadOptions {
+            repair_chunks: true,
+        };
+
+        // The declared length is right, the track just has no end of track event
+        let reader = RAMReader::new(Cursor::new(make_bytes(track.len() as u8))).unwrap();
+        let file = MIDIFile::from_reader(reader, &options, None).unwrap();
+        assert_eq!(file.track_count(), 1);
+        assert_eq!(file.chunk_repairs(), &[]);
+        assert_eq!(file.extra_chunks().len(), 1);
+        assert_eq!(&file.extra_chunks()[0].tag, b"XFIH");
+
+        // The declared length is wrong, so the track is ended at the next chunk
+        let reader = RAMReader::new(Cursor::new(make_bytes(0x20))).unwrap();
+        let file = MIDIFile::from_reader(reader, &options, None).unwrap();
+        assert_eq!(file.track_count(), 1);
+        assert_eq!(
+            file.chunk_repairs(),
+            &[ChunkRepair::TrackLength {
+                track: 0,
+                position: 22,
+                declared_len: 0x20,
+                repaired_len: track.len() as u32,
+            }]
+        );
+        assert_eq!(file.extra_chunks().len(), 1);
+        assert_eq!(file.extra_chunks()[0].data, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn open_mmap() {
+        let path = std::env::temp_dir().join("midi_toolkit_open_mmap.mid");
+        std::fs::write(&path, make_damaged_midi_bytes()).unwrap();
+
+        let file = MIDIFile::open_mmap(&path, None);
+        assert!(file.is_err());
+
+        let reader = unsafe { MmapReader::new(&std::fs::File::open(&path).unwrap()).unwrap() };
+        let options = LoadOptions {
+            repair_chunks: true,
+        };
+        let file = MIDIFile::from_reader(reader, &options, None).unwrap();
+        let events = to_vec_result(file.iter_all_events_merged()).unwrap();
+
+        drop(file);
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(
+            events,
+            vec![
+                Event::new_delta_note_on_event(0x10, 0, 0x40, 0x7F),
+                Event::new_delta_note_off_event(0x10, 0, 0x40, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn unexpected_chunk_error() {
+        let mut bytes = make_midi_bytes([0x01, 0xE0]);
+        bytes[14..18].copy_from_slice(&[0x00, 0xFF, 0x2F, 0x00]);
+
+        match MIDIFile::open_from_stream_in_ram(Cursor::new(bytes), None) {
+            Err(MIDILoadError::UnexpectedChunk {
+                position,
+                expected,
+                found,
+            }) => {
+                assert_eq!(position, 14);
+                assert_eq!(&expected, b"MTrk");
+                assert_eq!(found, [0x00, 0xFF, 0x2F, 0x00]);
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn unexpected_end_error() {
+        let mut bytes = make_midi_bytes([0x01, 0xE0]);
+        bytes.truncate(18);
+
+        match MIDIFile::open_from_stream_in_ram(Cursor::new(bytes), None) {
+            Err(MIDILoadError::UnexpectedEnd {
+                position,
+                length,
+                file_length,
+            }) => {
+                assert_eq!(position, 14);
+                assert_eq!(length, 8);
+                assert_eq!(file_length, 18);
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn file_too_big_error() {
+        /// A stream that claims to be larger than it is, so nothing gets allocated.
+        struct HugeStream;
+
+        impl std::io::Read for HugeStream {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                Ok(0)
+            }
+        }
+
+        impl std::io::Seek for HugeStream {
+            fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+                Ok(match pos {
+                    std::io::SeekFrom::End(_) => RAMReader::MAX_SUPPORTED_LENGTH + 1,
+                    _ => 0,
+                })
+            }
+        }
+
+        match RAMReader::new(HugeStream) {
+            Err(MIDILoadError::FileTooBig {
+                size,
+                max_supported,
+            }) => {
+                assert_eq!(size, RAMReader::MAX_SUPPORTED_LENGTH + 1);
+                assert_eq!(max_supported, RAMReader::MAX_SUPPORTED_LENGTH);
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn unified_error() {
+        fn count_events(bytes: Vec<u8>) -> Result<usize, MIDIError> {
+            let file = MIDIFile::open_from_stream_in_ram(Cursor::new(bytes), None)?;
+            let mut count = 0;
+            for event in file.iter_all_events_merged() {
+                event?;
+                count += 1;
+            }
+            Ok(count)
+        }
+
+        assert_eq!(count_events(make_midi_bytes([0x01, 0xE0])).unwrap(), 0);
+
+        let mut bytes = make_midi_bytes([0x01, 0xE0]);
+        bytes[7] = 4;
+        assert!(matches!(
+            count_events(bytes),
+            Err(MIDIError::Load(MIDILoadError::InvalidHeaderLength { .. }))
+        ));
+
+        let mut bytes = make_midi_bytes([0x01, 0xE0]);
+        bytes[21] = 2;
+        bytes.truncate(24);
+        assert!(matches!(count_events(bytes), Err(MIDIError::Parse(_))));
+    }
+
+    fn make_midi_bytes_with_extra_chunks() -> Vec<u8> {
+        let track = [
+            0x00, 0x90, 0x40, 0x7F, 0x10, 0x80, 0x40, 0x00, 0x00, 0xFF, 0x2F, 0x00,
+        ];
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"MThd");
+        bytes.extend_from_slice(&[0, 0, 0, 6, 0, 1, 0, 2, 0x01, 0xE0]);
+        bytes.extend_from_slice(b"XFIH");
+        bytes.extend_from_slice(&[0, 0, 0, 3, 1, 2, 3]);
+        bytes.extend_from_slice(b"MTrk");
+        bytes.extend_from_slice(&[0, 0, 0, track.len() as u8]);
+        bytes.extend_from_slice(&track);
+        bytes.extend_from_slice(b"XFKM");
+        bytes.extend_from_slice(&[0, 0, 0, 0]);
+        bytes.extend_from_slice(b"MTrk");
+        bytes.extend_from_slice(&[0, 0, 0, track.len() as u8]);
+        bytes.extend_from_slice(&track);
+        bytes.extend_from_slice(b"Vnd1");
+        bytes.extend_from_slice(&[0, 0, 0, 2, 0xAB, 0xCD]);
+        bytes
+    }
+
+    #[test]
+    fn skip_extra_chunks() {
+        let file = MIDIFile::open_from_stream_in_ram(
+            Cursor::new(make_midi_bytes_with_extra_chunks()),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(file.track_count(), 2);
+        assert_eq!(
+            file.extra_chunks(),
+            &[
+                ExtraChunk {
+                    tag: *b"XFIH",
+                    data: vec![1, 2, 3],
+                    preceding_tracks: 0,
+                },
+                ExtraChunk {
+                    tag: *b"XFKM",
+                    data: vec![],
+                    preceding_tracks: 1,
+                },
+                ExtraChunk {
+                    tag: *b"Vnd1",
+                    data: vec![0xAB, 0xCD],
+                    preceding_tracks: 2,
+                },
+            ]
+        );
+
+        for track in 0..2 {
+            let events = to_vec_result(file.iter_track(track)).unwrap();
+            assert_eq!(events.len(), 2);
+        }
+    }
+
+    #[test]
+    fn skip_longer_header() {
+        let mut bytes = make_midi_bytes([0x01, 0xE0]);
+        bytes[7] = 8;
+        bytes.splice(14..14, [0xFF, 0xFF]);
+
+        let file = MIDIFile::open_from_stream_in_ram(Cursor::new(bytes), None).unwrap();
+        assert_eq!(file.time_division(), TimeDivision::TicksPerQuarter(480));
+        assert_eq!(file.track_count(), 1);
+    }
+
+    #[test]
+    fn write_extra_chunks() {
+        let path = std::env::temp_dir().join("midi_toolkit_write_extra_chunks.mid");
+        let bytes = make_midi_bytes_with_extra_chunks();
+        let file = MIDIFile::open_from_stream_in_ram(Cursor::new(bytes.clone()), None).unwrap();
+
+        {
+            let mut writer = MIDIWriter::new(path.to_str().unwrap(), file.time_division()).unwrap();
+            for chunk in file.extra_chunks() {
+                writer.write_extra_chunk(chunk.clone()).unwrap();
+            }
+            for track in 0..file.track_count() {
+                let events = unwrap_items(file.iter_track(track as u32));
+                writer.open_next_track().write_events_iter(events).unwrap();
+            }
+            writer.end().unwrap();
+        }
+
+        let written = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(written, bytes);
+    }
+
+    fn make_running_status_midi_bytes() -> Vec<u8> {
+        let track = [
+            0x00, 0x90, 0x40, 0x7F, // note on
+            0x10, 0x41, 0x7F, // running status
+            0x10, 0xF0, 0x02, 0x43, 0x12, // unterminated sysex
+            0x05, 0xF7, 0x02, 0x00, 0xF7, // continuation
+            0x20, 0x80, 0x40, 0x00, //
+            0x00, 0x41, 0x00, // running status
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"MThd");
+        bytes.extend_from_slice(&[0, 0, 0, 6, 0, 1, 0, 1, 0x01, 0xE0]);
+        bytes.extend_from_slice(b"MTrk");
+        bytes.extend_from_slice(&[0, 0, 0, track.len() as u8]);
+        bytes.extend_from_slice(&track);
+        bytes
+    }
+
+    #[test]
+    fn resume_from_checkpoints() {
+        let bytes = make_running_status_midi_bytes();
+        let ram_file = MIDIFile::open_from_stream_in_ram(Cursor::new(bytes.clone()), None).unwrap();
+        let disk_file = MIDIFile::open_from_stream(Cursor::new(bytes), None).unwrap();
+
+        let all_events = to_vec_result(ram_file.iter_track(0)).unwrap();
+        assert_eq!(all_events.len(), 6);
+
+        for skip in 0..=all_events.len() {
+            let mut parser = ram_file.track_parser(0);
+            for _ in 0..skip {
+                parser.next().unwrap().unwrap();
+            }
+            let checkpoint = parser.checkpoint();
+            let expected_time: u64 = all_events[..skip].iter().map(|e| e.delta).sum();
+            assert_eq!(checkpoint.time(), expected_time);
+
+            let from_ram = to_vec_result(
+                ram_file
+                    .iter_track_from_checkpoint(0, checkpoint.clone())
+                    .unwrap(),
+            );
+            let from_disk =
+                to_vec_result(disk_file.iter_track_from_checkpoint(0, checkpoint).unwrap());
+            assert_eq!(from_ram.unwrap(), all_events[skip..]);
+            assert_eq!(from_disk.unwrap(), all_events[skip..]);
+        }
+    }
+
+    #[test]
+    fn reject_mismatched_checkpoints() {
+        let bytes = make_running_status_midi_bytes();
+        let ram_file = MIDIFile::open_from_stream_in_ram(Cursor::new(bytes.clone()), None).unwrap();
+        let disk_file = MIDIFile::open_from_stream(Cursor::new(bytes), None).unwrap();
+
+        let mut parser = ram_file.track_parser(0);
+        parser.next().unwrap().unwrap();
+        let checkpoint = parser.checkpoint();
+
+        // A track that doesn't exist
+        assert!(matches!(
+            ram_file.iter_track_from_checkpoint(1, checkpoint.clone()),
+            Err(MIDIParseError::InvalidCheckpoint)
+        ));
+
+        // A checkpoint from a bigger file, past the end of the track
+        let mut outside = checkpoint;
+        outside.reader_pos += 1000;
+        for result in [
+            ram_file
+                .iter_track_from_checkpoint(0, outside.clone())
+                .err(),
+            disk_file.iter_track_from_checkpoint(0, outside).err(),
+        ] {
+            assert!(matches!(result, Some(MIDIParseError::InvalidCheckpoint)));
+        }
+    }
+
+    fn make_seekable_midi_bytes() -> Vec<u8> {
+        let mut track0 = Vec::new();
+        Event::new_delta_tempo_event(0u64, 500000)
+            .serialize_event_with_delta(&mut track0)
+            .unwrap();
+        for i in 0..20 {
+            Event::new_delta_note_on_event(10u64, 0, i, 100)
+                .serialize_event_with_delta(&mut track0)
+                .unwrap();
+            if i == 5 {
+                Event::new_delta_tempo_event(0u64, 250000)
+                    .serialize_event_with_delta(&mut track0)
+                    .unwrap();
+            }
+        }
+        track0.extend_from_slice(&[0x00, 0xFF, 0x2F, 0x00]);
+
+        let mut track1 = Vec::new();
+        for i in 0..30 {
+            Event::new_delta_note_on_event(7u64, 1, i, 100)
+                .serialize_event_with_delta(&mut track1)
+                .unwrap();
+        }
+        track1.extend_from_slice(&[0x00, 0xFF, 0x2F, 0x00]);
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"MThd");
+        bytes.extend_from_slice(&[0, 0, 0, 6, 0, 1, 0, 2, 0x00, 0x0A]);
+        for track in [track0, track1] {
+            bytes.extend_from_slice(b"MTrk");
+            bytes.extend_from_slice(&(track.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(&track);
+        }
+        bytes
+    }
+
+    #[test]
+    fn rescale_file_tracks() {
+        let file = MIDIFile::open_from_stream_in_ram(Cursor::new(make_seekable_midi_bytes()), None)
+            .unwrap();
+
+        let original = file
+            .iter_all_tracks()
+            .map(|track| to_vec_result(track).unwrap());
+        let rescaled = file
+            .iter_all_tracks_rescaled(3, Rounding::Nearest)
+            .map(|track| to_vec_result(track).unwrap());
+        for (original, rescaled) in original.zip(rescaled) {
+            let mut time = 0;
+            let mut new_time = 0;
+            for (original, rescaled) in original.iter().zip(rescaled.iter()) {
+                time += original.delta;
+                new_time += rescaled.delta;
+                assert_eq!(new_time, (time * 3 + 5) / 10);
+                assert_eq!(original.event, rescaled.event);
+            }
+        }
+    }
+
+    #[test]
+    fn merged_file_notes() {
+        let file = MIDIFile::open_from_stream_in_ram(Cursor::new(make_seekable_midi_bytes()), None)
+            .unwrap();
+
+        let notes = to_vec_result(file.iter_all_notes_merged()).unwrap();
+        let track_notes = to_vec_result(file.iter_all_track_notes_merged()).unwrap();
+        assert_eq!(notes.len(), 50);
+        assert_eq!(track_notes.len(), 50);
+        assert!(notes.windows(2).all(|n| n[0].start <= n[1].start));
+
+        let mut expected_starts = [(10..=200).step_by(10), (7..=210).step_by(7)];
+        for note in track_notes.iter() {
+            // The notes were never ended, so they last until the end of their track
+            let expected_end = [200, 210][note.track as usize];
+            assert_eq!(note.channel() as u32, note.track);
+            assert_eq!(
+                Some(note.start()),
+                expected_starts[note.track as usize].next()
+            );
+            assert_eq!(note.end(), expected_end);
+        }
+    }
+
+    #[test]
+    fn iterate_from_seek_index() {
+        let file = MIDIFile::open_from_stream_in_ram(Cursor::new(make_seekable_midi_bytes()), None)
+            .unwrap();
+
+        let mut timed = Vec::new();
+        let mut time = 0;
+        for event in to_vec_result(file.iter_all_events_merged()).unwrap() {
+            time += event.delta;
+            timed.push((time, event.event));
+        }
+
+        for interval in [IndexInterval::Events(3), IndexInterval::Ticks(25)] {
+            let index = file.build_seek_index(interval).unwrap();
+            assert!(file.is_seek_index_valid(&index));
+
+            for tick in [0, 1, 49, 50, 51, 100, 140, 209, 210, 211, 500] {
+                let mut expected = Vec::new();
+                let mut prev_time = tick;
+                for (time, event) in timed.iter().filter(|(time, _)| *time >= tick) {
+                    expected.push(crate::sequence::event::Delta::new(
+                        time - prev_time,
+                        event.clone(),
+                    ));
+                    prev_time = *time;
+                }
+
+                let events =
+                    to_vec_result(file.iter_all_events_merged_from(&index, tick).unwrap()).unwrap();
+                assert_eq!(events, expected, "seeking to tick {tick} with {interval:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn seek_index_seconds_and_sidecar() {
+        let file = MIDIFile::open_from_stream_in_ram(Cursor::new(make_seekable_midi_bytes()), None)
+            .unwrap();
+        let index = file.build_seek_index(IndexInterval::Events(4)).unwrap();
+
+        // 10 ppq, 0.05s per tick at 500000 until tick 60, then 0.025s per tick
+        assert_eq!(index.seconds_to_ticks(1.0), 20);
+        assert_eq!(index.seconds_to_ticks(3.0), 60);
+        assert_eq!(index.seconds_to_ticks(4.0), 100);
+        assert_eq!(index.tempo_at(59), 500000);
+        assert_eq!(index.tempo_at(60), 250000);
+
+        let tempo_map = file.tempo_map().unwrap();
+        assert_eq!(&tempo_map, index.tempo_map());
+        assert_eq!(tempo_map.ticks_to_seconds(100), 4.0);
+
+        let from_seconds = to_vec_result(
+            file.iter_all_events_merged_from_seconds(&index, 4.0)
+                .unwrap(),
+        );
+        let from_ticks = to_vec_result(file.iter_all_events_merged_from(&index, 100).unwrap());
+        assert_eq!(from_seconds.unwrap(), from_ticks.unwrap());
+
+        let mut bytes = Vec::new();
+        index.write_to(&mut bytes).unwrap();
+        let loaded = SeekIndex::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(loaded, index);
+        assert!(file.is_seek_index_valid(&loaded));
+
+        assert!(SeekIndex::read_from(&mut &bytes[1..]).is_err());
+    }
+
+    #[test]
+    fn reject_corrupt_seek_index() {
+        let file = MIDIFile::open_from_stream_in_ram(Cursor::new(make_seekable_midi_bytes()), None)
+            .unwrap();
+        let index = file.build_seek_index(IndexInterval::Events(4)).unwrap();
+
+        // A checkpoint that points past the end of its track
+        let mut outside = index.clone();
+        outside.tracks[0][1].reader_pos = index.file_len + 10;
+        assert!(!file.is_seek_index_valid(&outside));
+        assert!(matches!(
+            file.iter_all_events_merged_from(&outside, 50),
+            Err(MIDIParseError::InvalidSeekIndex)
+        ));
+
+        // Checkpoints out of order can't be loaded
+        let mut unsorted = index.clone();
+        unsorted.tracks[1].swap(1, 2);
+        let mut bytes = Vec::new();
+        unsorted.write_to(&mut bytes).unwrap();
+        assert!(SeekIndex::read_from(&mut bytes.as_slice()).is_err());
     }
 }
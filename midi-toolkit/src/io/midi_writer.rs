@@ -7,7 +7,7 @@ use std::{
 
 use crate::events::SerializeEventWithDelta;
 
-use super::errors::MIDIWriteError;
+use super::{errors::MIDIWriteError, midi_file::ExtraChunk, time_division::TimeDivision};
 
 pub trait WriteSeek: Write + Seek {}
 impl WriteSeek for File {}
@@ -24,6 +24,7 @@ struct TrackStatus {
     next_init_track: i32,
     next_write_track: i32,
     queued_writes: HashMap<i32, QueuedOutput>,
+    queued_chunks: Vec<ExtraChunk>,
 }
 
 pub struct MIDIWriter {
@@ -60,22 +61,54 @@ fn flush_track(writer: &mut Box<dyn WriteSeek>, mut output: QueuedOutput) -> Res
     Ok(())
 }
 
+fn flush_chunk(writer: &mut Box<dyn WriteSeek>, chunk: &ExtraChunk) -> Result<(), io::Error> {
+    writer.write_all(&chunk.tag)?;
+    writer.write_all(&encode_u32(chunk.data.len() as u32))?;
+    writer.write_all(&chunk.data)?;
+    Ok(())
+}
+
+/// Writes all the queued chunks that should come before the given track, or all of them if `None`.
+fn flush_chunks_before(
+    writer: &mut Box<dyn WriteSeek>,
+    status: &mut TrackStatus,
+    track_id: Option<i32>,
+) -> Result<(), io::Error> {
+    let (ready, waiting) = status
+        .queued_chunks
+        .drain(..)
+        .partition::<Vec<_>, _>(|chunk| {
+            track_id.is_none_or(|track_id| chunk.preceding_tracks as i64 <= track_id as i64)
+        });
+    status.queued_chunks = waiting;
+    for chunk in ready.iter() {
+        flush_chunk(writer, chunk)?;
+    }
+    Ok(())
+}
+
 impl MIDIWriter {
-    pub fn new(filename: &str, ppq: u16) -> Result<MIDIWriter, MIDIWriteError> {
+    /// Creates a new MIDI file at the given path.
+    ///
+    /// The time division can either be a plain ppq `u16` or a [`TimeDivision`].
+    pub fn new(
+        filename: &str,
+        division: impl Into<TimeDivision>,
+    ) -> Result<MIDIWriter, MIDIWriteError> {
         let reader = File::create(filename)?;
-        MIDIWriter::new_from_stram(Box::new(reader), ppq)
+        MIDIWriter::new_from_stram(Box::new(reader), division)
     }
 
     pub fn new_from_stram(
         mut output: Box<dyn WriteSeek>,
-        ppq: u16,
+        division: impl Into<TimeDivision>,
     ) -> Result<MIDIWriter, MIDIWriteError> {
         output.seek(SeekFrom::Start(0))?;
         output.write_all("MThd".as_bytes())?;
         output.write_all(&encode_u32(6))?;
         output.write_all(&encode_u16(1))?;
         output.write_all(&encode_u16(0))?;
-        output.write_all(&encode_u16(ppq))?;
+        output.write_all(&encode_u16(division.into().to_u16()))?;
 
         Ok(MIDIWriter {
             output: Some(Mutex::new(output)),
@@ -85,6 +118,7 @@ impl MIDIWriter {
                 next_write_track: 0,
                 queued_writes: HashMap::new(),
                 written_tracks: HashSet::new(),
+                queued_chunks: Vec::new(),
             }),
         })
     }
@@ -104,8 +138,8 @@ impl MIDIWriter {
         Ok(())
     }
 
-    pub fn write_ppq(&self, ppq: u16) -> Result<(), MIDIWriteError> {
-        Ok(self.write_u16_at(12, ppq)?)
+    pub fn write_ppq(&self, division: impl Into<TimeDivision>) -> Result<(), MIDIWriteError> {
+        Ok(self.write_u16_at(12, division.into().to_u16())?)
     }
 
     pub fn write_format(&self, ppq: u16) -> Result<(), MIDIWriteError> {
@@ -135,6 +169,21 @@ impl MIDIWriter {
         }
     }
 
+    /// Writes a chunk that isn't a track, such as one from [`MIDIFile::extra_chunks`](crate::io::MIDIFile::extra_chunks).
+    ///
+    /// The chunk is placed right before the track at index `chunk.preceding_tracks`, or after the last track
+    /// if there is no such track. If that track has already been written, the chunk is written immediately.
+    pub fn write_extra_chunk(&self, chunk: ExtraChunk) -> Result<(), MIDIWriteError> {
+        let mut status = self.tracks.lock().unwrap();
+        if chunk.preceding_tracks as i64 <= status.next_write_track as i64 {
+            let mut writer = self.get_writer().lock().unwrap();
+            flush_chunk(&mut writer, &chunk)?;
+        } else {
+            status.queued_chunks.push(chunk);
+        }
+        Ok(())
+    }
+
     fn add_opened_track(&self, track_id: i32) {
         let mut tracks = self.tracks.lock().unwrap();
         if tracks.written_tracks.contains(&track_id) || !tracks.opened_tracks.insert(track_id) {
@@ -143,7 +192,7 @@ impl MIDIWriter {
     }
 
     pub fn end(&mut self) -> Result<(), MIDIWriteError> {
-        let tracks = self.tracks.lock().unwrap();
+        let mut tracks = self.tracks.lock().unwrap();
         if !tracks.opened_tracks.is_empty() {
             let unwritten: Vec<&i32> = tracks.queued_writes.keys().collect();
             panic!("Not all tracks have been ended! Make sure you drop or call .end() on each track before ending the MIDIWriter\nMissing tracks {:?}", unwritten);
@@ -159,6 +208,8 @@ impl MIDIWriter {
             );
         }
 
+        flush_chunks_before(&mut self.get_writer().lock().unwrap(), &mut tracks, None)?;
+
         let track_count = tracks.written_tracks.len();
         self.write_ntrks(track_count.min(u16::MAX as usize) as u16)?;
 
@@ -206,6 +257,7 @@ impl<'a> TrackWriter<'a> {
                 match status.queued_writes.remove_entry(&next_write_track) {
                     None => break,
                     Some(output) => {
+                        flush_chunks_before(&mut writer, &mut status, Some(next_write_track))?;
                         flush_track(&mut writer, output.1)?;
                         status.next_write_track += 1;
                     }
@@ -1,6 +1,9 @@
 use crossbeam_channel::{bounded, unbounded, Sender};
+use memmap2::Mmap;
 use std::{
+    fs::File,
     io::{self, Read, Seek, SeekFrom},
+    ops::Deref,
     sync::Arc,
     thread::{self, JoinHandle},
 };
@@ -22,6 +25,15 @@ pub struct RAMReader {
     pos: usize,
 }
 
+/// A reader backed by a read-only memory map of the file.
+///
+/// Each track reader gets a zero-copy view into the map, so parsing is as fast as with [`RAMReader`]
+/// without copying the whole file into memory upfront or being limited in size.
+#[derive(Debug)]
+pub struct MmapReader {
+    mmap: Arc<Mmap>,
+}
+
 pub struct ReadCommand {
     destination: Sender<Result<Vec<u8>, io::Error>>,
     buffer: Vec<u8>,
@@ -100,6 +112,18 @@ impl BufferReadProvider {
     }
 }
 
+fn check_read_bounds(pos: u64, count: usize, file_length: u64) -> Result<(), MIDILoadError> {
+    if pos + count as u64 > file_length {
+        Err(MIDILoadError::UnexpectedEnd {
+            position: pos,
+            length: count as u64,
+            file_length,
+        })
+    } else {
+        Ok(())
+    }
+}
+
 fn get_reader_len<T: Seek>(reader: &mut T) -> Result<u64, MIDILoadError> {
     let pos = reader.seek(SeekFrom::End(0))?;
     reader.seek(SeekFrom::Start(0))?;
@@ -124,18 +148,20 @@ impl DiskReader {
 }
 
 impl RAMReader {
+    /// The maximum file size that can be loaded into memory.
+    pub const MAX_SUPPORTED_LENGTH: u64 = 2147483648;
+
     pub fn new<T: Read + Seek>(mut reader: T) -> Result<RAMReader, MIDILoadError> {
         let len = get_reader_len(&mut reader);
 
         match len {
             Err(e) => Err(e),
             Ok(length) => {
-                let max_supported: u64 = 2147483648;
-                if length > max_supported {
-                    panic!(
-                        "The maximum length allowed for a memory loaded MIDI file is {}",
-                        max_supported
-                    );
+                if length > Self::MAX_SUPPORTED_LENGTH {
+                    return Err(MIDILoadError::FileTooBig {
+                        size: length,
+                        max_supported: Self::MAX_SUPPORTED_LENGTH,
+                    });
                 }
 
                 let mut bytes = vec![0; length as usize];
@@ -149,12 +175,24 @@ impl RAMReader {
     }
 
     pub fn read_byte(&mut self) -> Result<u8, MIDILoadError> {
-        let b = self.bytes.get(self.pos);
+        check_read_bounds(self.pos as u64, 1, self.len())?;
+        let b = self.bytes[self.pos];
         self.pos += 1;
-        match b {
-            Some(v) => Ok(*v),
-            None => Err(MIDILoadError::CorruptChunks),
-        }
+        Ok(b)
+    }
+}
+
+impl MmapReader {
+    /// Memory maps the file.
+    ///
+    /// ## Safety
+    ///
+    /// The file must not be modified or truncated while it is mapped, see [`Mmap::map`].
+    pub unsafe fn new(file: &File) -> Result<MmapReader, MIDILoadError> {
+        let mmap = Mmap::map(file)?;
+        Ok(MmapReader {
+            mmap: Arc::new(mmap),
+        })
     }
 }
 
@@ -173,17 +211,35 @@ pub trait MIDIReader: Debug {
         self.len() == 0
     }
 
-    fn open_reader(&self, track_number: Option<u32>, start: u64, len: u64) -> Self::ByteReader;
+    fn open_reader(&self, track_number: Option<u32>, start: u64, len: u64) -> Self::ByteReader {
+        self.open_reader_at(track_number, start, len, start)
+    }
+
+    /// Opens a reader for the track at `[start, start + len)`, that starts reading from `pos` inside of the track.
+    fn open_reader_at(
+        &self,
+        track_number: Option<u32>,
+        start: u64,
+        len: u64,
+        pos: u64,
+    ) -> Self::ByteReader;
 }
 
 impl MIDIReader for DiskReader {
     type ByteReader = DiskTrackReader;
 
-    fn open_reader(&self, track_number: Option<u32>, start: u64, len: u64) -> DiskTrackReader {
-        DiskTrackReader::new(track_number, self.reader.clone(), start, len)
+    fn open_reader_at(
+        &self,
+        track_number: Option<u32>,
+        start: u64,
+        len: u64,
+        pos: u64,
+    ) -> DiskTrackReader {
+        DiskTrackReader::new_at(track_number, self.reader.clone(), start, len, pos)
     }
 
     fn read_bytes_to(&self, pos: u64, bytes: Vec<u8>) -> Result<Vec<u8>, MIDILoadError> {
+        check_read_bounds(pos, bytes.len(), self.len())?;
         Ok(self.reader.read_sync(bytes, pos)?)
     }
 
@@ -195,26 +251,25 @@ impl MIDIReader for DiskReader {
 impl MIDIReader for RAMReader {
     type ByteReader = FullRamTrackReader;
 
-    fn open_reader<'a>(
+    fn open_reader_at(
         &self,
         track_number: Option<u32>,
         start: u64,
         len: u64,
+        pos: u64,
     ) -> FullRamTrackReader {
-        FullRamTrackReader {
+        FullRamTrackReader::new_at(
             track_number,
-            start: start as usize,
-            pos: start as usize,
-            end: (start + len) as usize,
-            bytes: self.bytes.clone(),
-        }
+            self.bytes.clone(),
+            start as usize,
+            (start + len) as usize,
+            pos as usize,
+        )
     }
 
     fn read_bytes_to(&self, pos: u64, mut bytes: Vec<u8>) -> Result<Vec<u8>, MIDILoadError> {
         let count = bytes.len();
-        if pos + count as u64 > self.len() {
-            return Err(MIDILoadError::CorruptChunks);
-        }
+        check_read_bounds(pos, count, self.len())?;
 
         bytes[..].clone_from_slice(&self.bytes[pos as usize..pos as usize + count]);
 
@@ -226,6 +281,39 @@ impl MIDIReader for RAMReader {
     }
 }
 
+impl MIDIReader for MmapReader {
+    type ByteReader = MmapTrackReader;
+
+    fn open_reader_at(
+        &self,
+        track_number: Option<u32>,
+        start: u64,
+        len: u64,
+        pos: u64,
+    ) -> MmapTrackReader {
+        FullRamTrackReader::new_at(
+            track_number,
+            self.mmap.clone(),
+            start as usize,
+            (start + len) as usize,
+            pos as usize,
+        )
+    }
+
+    fn read_bytes_to(&self, pos: u64, mut bytes: Vec<u8>) -> Result<Vec<u8>, MIDILoadError> {
+        let count = bytes.len();
+        check_read_bounds(pos, count, self.len())?;
+
+        bytes[..].clone_from_slice(&self.mmap[pos as usize..pos as usize + count]);
+
+        Ok(bytes)
+    }
+
+    fn len(&self) -> u64 {
+        self.mmap.len() as u64
+    }
+}
+
 pub trait TrackReader: Send + Sync {
     /// The stored track number for diagnostic purposes
     fn track_number(&self) -> Option<u32>;
@@ -251,32 +339,54 @@ pub struct DiskTrackReader {
     receiver_sender: Option<Sender<Result<Vec<u8>, io::Error>>>, // Becomes None when there's nothing left to read
 }
 
-pub struct FullRamTrackReader {
+/// A track reader over bytes that are already in memory (or memory mapped).
+pub struct FullRamTrackReader<B: Deref<Target = [u8]> = Vec<u8>> {
     /// The track number and start are only for error logging purposes
     track_number: Option<u32>,
     start: usize,
 
-    bytes: Arc<Vec<u8>>,
+    bytes: Arc<B>,
     pos: usize,
     end: usize,
 }
 
-impl FullRamTrackReader {
+/// The track reader of [`MmapReader`].
+pub type MmapTrackReader = FullRamTrackReader<Mmap>;
+
+impl<B: Deref<Target = [u8]>> FullRamTrackReader<B> {
     pub fn new(
         track_number: Option<u32>,
-        bytes: Arc<Vec<u8>>,
+        bytes: Arc<B>,
         start: usize,
         end: usize,
-    ) -> FullRamTrackReader {
+    ) -> FullRamTrackReader<B> {
+        Self::new_at(track_number, bytes, start, end, start)
+    }
+
+    /// Creates a reader for the track at `[start, end)` that starts reading from `pos`,
+    /// for example from a [`ParserCheckpoint::reader_pos`](crate::io::ParserCheckpoint::reader_pos).
+    pub fn new_at(
+        track_number: Option<u32>,
+        bytes: Arc<B>,
+        start: usize,
+        end: usize,
+        pos: usize,
+    ) -> FullRamTrackReader<B> {
+        assert!(
+            start <= pos && pos <= end,
+            "Reader pos is outside of the track"
+        );
         FullRamTrackReader {
             track_number,
             bytes,
             start,
-            pos: start,
+            pos,
             end,
         }
     }
+}
 
+impl FullRamTrackReader {
     pub fn new_from_vec(track_number: Option<u32>, bytes: Vec<u8>) -> FullRamTrackReader {
         let len = bytes.len();
         FullRamTrackReader {
@@ -289,7 +399,7 @@ impl FullRamTrackReader {
     }
 }
 
-impl TrackReader for FullRamTrackReader {
+impl<B: Deref<Target = [u8]> + Send + Sync> TrackReader for FullRamTrackReader<B> {
     #[inline(always)]
     fn read(&mut self) -> Result<u8, MIDIParseError> {
         if self.pos == self.end {
@@ -369,6 +479,23 @@ impl DiskTrackReader {
         start: u64,
         len: u64,
     ) -> DiskTrackReader {
+        Self::new_at(track_number, reader, start, len, start)
+    }
+
+    /// Creates a reader for the track at `[start, start + len)` that starts reading from `pos`,
+    /// for example from a [`ParserCheckpoint::reader_pos`](crate::io::ParserCheckpoint::reader_pos).
+    pub fn new_at(
+        track_number: Option<u32>,
+        reader: Arc<BufferReadProvider>,
+        start: u64,
+        len: u64,
+        pos: u64,
+    ) -> DiskTrackReader {
+        assert!(
+            start <= pos && pos <= start + len,
+            "Reader pos is outside of the track"
+        );
+        let offset = pos - start;
         let buffer_count = 3;
 
         let (send, receive) = unbounded();
@@ -379,9 +506,9 @@ impl DiskTrackReader {
             start,
             len,
             buffer: None,
-            buffer_start: 0,
+            buffer_start: offset,
             buffer_pos: 0,
-            unrequested_data_start: 0,
+            unrequested_data_start: offset,
             receiver: DelayedReceiver::new(receive),
             receiver_sender: Some(send),
         };
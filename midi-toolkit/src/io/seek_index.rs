@@ -0,0 +1,183 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use crate::sequence::event::TempoMap;
+
+use super::{time_division::TimeDivision, track_parser::ParserCheckpoint};
+
+/// How often checkpoints are recorded while building a [`SeekIndex`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexInterval {
+    /// Record a checkpoint every N events
+    Events(u32),
+    /// Record a checkpoint at the first event after every N ticks
+    Ticks(u64),
+}
+
+/// Checkpoints into every track of a file, for jumping to a tick or a time without reparsing
+/// everything before it. Built with [`MIDIFile::build_seek_index`](crate::io::MIDIFile::build_seek_index).
+///
+/// The index can be saved next to the MIDI file with [`save`](SeekIndex::save), so that it doesn't have
+/// to be built again when the file is reopened.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeekIndex {
+    pub(crate) file_len: u64,
+    /// The checkpoints of each track, sorted by time. The first checkpoint is always the start of the track.
+    pub(crate) tracks: Vec<Vec<ParserCheckpoint>>,
+    /// The tempo events of every track in the file
+    pub(crate) tempo_map: TempoMap<u64>,
+}
+
+const MAGIC: &[u8; 4] = b"MTKI";
+const VERSION: u32 = 1;
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+impl SeekIndex {
+    pub fn track_count(&self) -> usize {
+        self.tracks.len()
+    }
+
+    /// The checkpoints recorded for a track.
+    pub fn track_checkpoints(&self, track: u32) -> &[ParserCheckpoint] {
+        &self.tracks[track as usize]
+    }
+
+    /// The last checkpoint of the track that comes strictly before the tick, so that no events at the tick are missed.
+    pub fn checkpoint_before(&self, track: u32, tick: u64) -> &ParserCheckpoint {
+        let checkpoints = &self.tracks[track as usize];
+        let after = checkpoints.partition_point(|c| c.time() < tick);
+        &checkpoints[after.saturating_sub(1)]
+    }
+
+    /// The tempo that is active at the tick, in microseconds per quarter note.
+    pub fn tempo_at(&self, tick: u64) -> u32 {
+        self.tempo_map.tempo_at(tick)
+    }
+
+    /// A [`TempoMap`] of the tempo events in the file.
+    pub fn tempo_map(&self) -> &TempoMap<u64> {
+        &self.tempo_map
+    }
+
+    /// Converts a time in seconds from the start of the file into the closest tick, using the tempo events of the file.
+    pub fn seconds_to_ticks(&self, seconds: f64) -> u64 {
+        self.tempo_map.seconds_to_ticks(seconds).round() as u64
+    }
+
+    /// Writes the index in a compact binary format.
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&self.file_len.to_le_bytes())?;
+        writer.write_all(&self.tempo_map.division().to_u16().to_le_bytes())?;
+
+        writer.write_all(&(self.tracks.len() as u32).to_le_bytes())?;
+        for track in self.tracks.iter() {
+            writer.write_all(&(track.len() as u32).to_le_bytes())?;
+            for checkpoint in track.iter() {
+                writer.write_all(&checkpoint.reader_pos.to_le_bytes())?;
+                writer.write_all(&checkpoint.time.to_le_bytes())?;
+                writer.write_all(&checkpoint.pushback.to_le_bytes())?;
+                writer.write_all(&[
+                    checkpoint.prev_command,
+                    checkpoint.sysex_open as u8 | (checkpoint.ended as u8) << 1,
+                ])?;
+            }
+        }
+
+        let tempos = self.tempo_map.changes();
+        writer.write_all(&(tempos.len() as u32).to_le_bytes())?;
+        for change in tempos.iter() {
+            writer.write_all(&change.tick.to_le_bytes())?;
+            writer.write_all(&change.tempo.to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads an index that was written with [`write_to`](SeekIndex::write_to).
+    pub fn read_from(reader: &mut impl Read) -> io::Result<SeekIndex> {
+        if &read_array::<4>(reader)? != MAGIC {
+            return Err(invalid_data("Not a seek index file"));
+        }
+        if u32::from_le_bytes(read_array(reader)?) != VERSION {
+            return Err(invalid_data("Unsupported seek index version"));
+        }
+        let file_len = u64::from_le_bytes(read_array(reader)?);
+        let division = TimeDivision::from_u16(u16::from_le_bytes(read_array(reader)?));
+
+        let track_count = u32::from_le_bytes(read_array(reader)?);
+        let mut tracks = Vec::new();
+        for _ in 0..track_count {
+            let checkpoint_count = u32::from_le_bytes(read_array(reader)?);
+            let mut checkpoints = Vec::new();
+            for _ in 0..checkpoint_count {
+                let reader_pos = u64::from_le_bytes(read_array(reader)?);
+                let time = u64::from_le_bytes(read_array(reader)?);
+                let pushback = i16::from_le_bytes(read_array(reader)?);
+                let [prev_command, flags] = read_array(reader)?;
+                checkpoints.push(ParserCheckpoint {
+                    pushback,
+                    prev_command,
+                    sysex_open: flags & 1 != 0,
+                    reader_pos,
+                    time,
+                    ended: flags & 2 != 0,
+                });
+            }
+            if checkpoints.is_empty() {
+                return Err(invalid_data("Track without checkpoints in seek index"));
+            }
+            let is_sorted = checkpoints
+                .windows(2)
+                .all(|c| c[0].reader_pos < c[1].reader_pos && c[0].time <= c[1].time);
+            if !is_sorted {
+                return Err(invalid_data("Unsorted checkpoints in seek index"));
+            }
+            tracks.push(checkpoints);
+        }
+
+        let tempo_count = u32::from_le_bytes(read_array(reader)?);
+        let mut tempos = Vec::new();
+        for _ in 0..tempo_count {
+            let tick = u64::from_le_bytes(read_array(reader)?);
+            let tempo = u32::from_le_bytes(read_array(reader)?);
+            tempos.push((tick, tempo));
+        }
+        if !tempos.windows(2).all(|t| t[0].0 <= t[1].0) {
+            return Err(invalid_data("Unsorted tempo events in seek index"));
+        }
+
+        Ok(SeekIndex {
+            file_len,
+            tracks,
+            tempo_map: TempoMap::from_absolute_tempos(tempos, division),
+        })
+    }
+
+    /// Saves the index to a sidecar file.
+    pub fn save(&self, filename: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(filename)?);
+        self.write_to(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Loads an index from a sidecar file. Use [`MIDIFile::is_seek_index_valid`](crate::io::MIDIFile::is_seek_index_valid)
+    /// to check that it still matches the MIDI file.
+    pub fn load(filename: impl AsRef<Path>) -> io::Result<SeekIndex> {
+        let mut reader = BufReader::new(File::open(filename)?);
+        SeekIndex::read_from(&mut reader)
+    }
+}
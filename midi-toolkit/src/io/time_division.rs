@@ -0,0 +1,191 @@
+/// The time division stored in the header of a MIDI file.
+///
+/// Most files use [`TimeDivision::TicksPerQuarter`], where the tick length depends on the tempo.
+/// SMPTE files (negative high byte in the header) have a fixed tick length and ignore tempo events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimeDivision {
+    /// Musical time, in ticks per quarter note (ppq)
+    TicksPerQuarter(u16),
+    /// Absolute time, in ticks per SMPTE frame.
+    ///
+    /// `fps` is one of 24, 25, 29 (29.97 drop frame) or 30.
+    Smpte { fps: u8, ticks_per_frame: u8 },
+}
+
+impl TimeDivision {
+    /// Parses the division word from a MIDI header.
+    ///
+    /// Any SMPTE frame rate is accepted, use [`is_valid`](#method.is_valid) to check it.
+    pub fn from_u16(val: u16) -> TimeDivision {
+        if val & 0x8000 == 0 {
+            TimeDivision::TicksPerQuarter(val)
+        } else {
+            let fps = ((val >> 8) as u8).wrapping_neg();
+            TimeDivision::Smpte {
+                fps,
+                ticks_per_frame: (val & 0xFF) as u8,
+            }
+        }
+    }
+
+    /// Encodes the division into the word stored in a MIDI header.
+    pub fn to_u16(self) -> u16 {
+        match self {
+            TimeDivision::TicksPerQuarter(ppq) => ppq & 0x7FFF,
+            TimeDivision::Smpte {
+                fps,
+                ticks_per_frame,
+            } => ((fps.wrapping_neg() as u16) << 8) | ticks_per_frame as u16,
+        }
+    }
+
+    /// Whether an SMPTE division uses one of the frame rates allowed by the standard. Always true for ppq divisions.
+    pub fn is_valid(&self) -> bool {
+        match self {
+            TimeDivision::TicksPerQuarter(_) => true,
+            TimeDivision::Smpte { fps, .. } => matches!(fps, 24 | 25 | 29 | 30),
+        }
+    }
+
+    /// The ticks per quarter note, if the division is musical.
+    pub fn ppq(&self) -> Option<u16> {
+        match self {
+            TimeDivision::TicksPerQuarter(ppq) => Some(*ppq),
+            TimeDivision::Smpte { .. } => None,
+        }
+    }
+
+    pub fn is_smpte(&self) -> bool {
+        matches!(self, TimeDivision::Smpte { .. })
+    }
+
+    /// The real frame rate of an SMPTE division, taking 29.97 drop frame into account.
+    pub fn frames_per_second(&self) -> Option<f64> {
+        match self {
+            TimeDivision::TicksPerQuarter(_) => None,
+            TimeDivision::Smpte { fps: 29, .. } => Some(30000.0 / 1001.0),
+            TimeDivision::Smpte { fps, .. } => Some(*fps as f64),
+        }
+    }
+
+    /// The exact length of a single tick in seconds as a `(numerator, denominator)` fraction,
+    /// given the current tempo in microseconds per quarter note.
+    ///
+    /// The denominator doesn't depend on the tempo. The tempo is ignored for SMPTE divisions.
+    pub fn tick_seconds_ratio(&self, tempo: u32) -> (u64, u64) {
+        match *self {
+            TimeDivision::TicksPerQuarter(ppq) => (tempo as u64, 1000000 * ppq as u64),
+            TimeDivision::Smpte {
+                fps: 29,
+                ticks_per_frame,
+            } => (1001, 30000 * ticks_per_frame as u64),
+            TimeDivision::Smpte {
+                fps,
+                ticks_per_frame,
+            } => (1, fps as u64 * ticks_per_frame as u64),
+        }
+    }
+
+    /// The length of a single tick in seconds, given the current tempo in microseconds per quarter note.
+    ///
+    /// The tempo is ignored for SMPTE divisions.
+    pub fn tick_seconds(&self, tempo: u32) -> f64 {
+        match self {
+            TimeDivision::TicksPerQuarter(ppq) => (tempo as f64 / *ppq as f64) / 1000000.0,
+            TimeDivision::Smpte {
+                ticks_per_frame, ..
+            } => 1.0 / (self.frames_per_second().unwrap() * *ticks_per_frame as f64),
+        }
+    }
+}
+
+impl From<u16> for TimeDivision {
+    fn from(val: u16) -> Self {
+        TimeDivision::from_u16(val)
+    }
+}
+
+impl From<TimeDivision> for u16 {
+    fn from(division: TimeDivision) -> Self {
+        division.to_u16()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TimeDivision;
+
+    #[test]
+    fn parse_division_word() {
+        assert_eq!(
+            TimeDivision::from_u16(960),
+            TimeDivision::TicksPerQuarter(960)
+        );
+        assert_eq!(
+            TimeDivision::from_u16(0xE728),
+            TimeDivision::Smpte {
+                fps: 25,
+                ticks_per_frame: 40
+            }
+        );
+        assert_eq!(
+            TimeDivision::from_u16(0xE350),
+            TimeDivision::Smpte {
+                fps: 29,
+                ticks_per_frame: 80
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_smpte_rates() {
+        let division = TimeDivision::from_u16(0x8028);
+        assert_eq!(
+            division,
+            TimeDivision::Smpte {
+                fps: 128,
+                ticks_per_frame: 40
+            }
+        );
+        assert!(!division.is_valid());
+        assert_eq!(division.to_u16(), 0x8028);
+
+        assert!(!TimeDivision::from_u16(0xFF28).is_valid());
+        assert!(TimeDivision::from_u16(0xE228).is_valid());
+    }
+
+    #[test]
+    fn encode_division_word() {
+        for word in [96u16, 960, 0x7FFF, 0xE828, 0xE728, 0xE350, 0xE250] {
+            assert_eq!(TimeDivision::from_u16(word).to_u16(), word);
+        }
+    }
+
+    #[test]
+    fn tick_seconds() {
+        let ppq = TimeDivision::TicksPerQuarter(100);
+        assert_eq!(ppq.tick_seconds(500000), 0.005);
+
+        let smpte = TimeDivision::Smpte {
+            fps: 25,
+            ticks_per_frame: 40,
+        };
+        assert_eq!(smpte.tick_seconds(500000), 0.001);
+        assert_eq!(smpte.tick_seconds(250000), 0.001);
+    }
+
+    #[test]
+    fn tick_seconds_ratio() {
+        let ppq = TimeDivision::TicksPerQuarter(100);
+        assert_eq!(ppq.tick_seconds_ratio(500000), (500000, 100000000));
+
+        let drop_frame = TimeDivision::Smpte {
+            fps: 29,
+            ticks_per_frame: 80,
+        };
+        let (numerator, denominator) = drop_frame.tick_seconds_ratio(500000);
+        assert!(
+            (numerator as f64 / denominator as f64 - drop_frame.tick_seconds(500000)).abs() < 1e-15
+        );
+    }
+}
@@ -1,52 +1,195 @@
+use std::sync::Arc;
+
 use crate::{events::*, sequence::event::Delta};
 
-use super::{errors::MIDIParseError, readers::TrackReader};
+use super::{
+    errors::{MIDIParseError, ParseWarning},
+    readers::TrackReader,
+};
+
+/// Options for how a [`TrackParser`] handles malformed tracks.
+///
+/// By default, parsing is strict and the track ends at the first error.
+/// Every recovery that happens in a lenient mode is reported as a [`ParseWarning`] to the warning callback.
+#[derive(Clone, Default)]
+pub struct ParseOptions {
+    /// Skip stray data bytes that appear where a status byte is expected and there is no running status.
+    pub skip_stray_bytes: bool,
+    /// Skip meta events that have an invalid length instead of erroring.
+    pub skip_corrupt_events: bool,
+    /// Clamp channel event data bytes above 0x7F into range.
+    pub clamp_data_bytes: bool,
+    /// End the track without an error if it ends in the middle of an event.
+    pub allow_truncated_tracks: bool,
+    warning_callback: Option<Arc<dyn Fn(ParseWarning) + Send + Sync>>,
+}
+
+impl ParseOptions {
+    /// Fail on the first malformed event, the default.
+    pub fn strict() -> Self {
+        Self::default()
+    }
+
+    /// Recover from every malformed event that can be recovered from.
+    pub fn lenient() -> Self {
+        Self {
+            skip_stray_bytes: true,
+            skip_corrupt_events: true,
+            clamp_data_bytes: true,
+            allow_truncated_tracks: true,
+            warning_callback: None,
+        }
+    }
+
+    /// Set a callback that receives a [`ParseWarning`] for every recovery.
+    ///
+    /// The callback is shared between all the tracks of a file, which may be parsed on different threads.
+    pub fn with_warning_callback(
+        mut self,
+        callback: impl Fn(ParseWarning) + Send + Sync + 'static,
+    ) -> Self {
+        self.warning_callback = Some(Arc::new(callback));
+        self
+    }
+
+    fn warn(&self, warning: ParseWarning) {
+        if let Some(callback) = &self.warning_callback {
+            callback(warning);
+        }
+    }
+}
+
+impl std::fmt::Debug for ParseOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParseOptions")
+            .field("skip_stray_bytes", &self.skip_stray_bytes)
+            .field("skip_corrupt_events", &self.skip_corrupt_events)
+            .field("clamp_data_bytes", &self.clamp_data_bytes)
+            .field("allow_truncated_tracks", &self.allow_truncated_tracks)
+            .field("warning_callback", &self.warning_callback.is_some())
+            .finish()
+    }
+}
 
 pub struct TrackParser<T: TrackReader> {
     reader: T,
     pushback: i16,
+    /// The position of the pushed back byte in the file, for warnings
+    pushback_pos: u64,
     prev_command: u8,
     errored: bool,
+    carried_delta: u64,
+    /// Whether a system exclusive message was started and hasn't been terminated yet,
+    /// meaning that `F7` events are continuation packets rather than escapes
+    sysex_open: bool,
+    time: u64,
+    options: ParseOptions,
 }
 
+/// The state of a [`TrackParser`] in between two events, which can be used to resume parsing
+/// from that point with [`TrackParser::from_checkpoint`] instead of from the start of the track.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ParserCheckpoint {
-    pushback: i16,
-    prev_command: u8,
-    reader_pos: u64,
-    ended: bool,
+    pub(crate) pushback: i16,
+    pub(crate) prev_command: u8,
+    pub(crate) sysex_open: bool,
+    pub(crate) reader_pos: u64,
+    pub(crate) time: u64,
+    pub(crate) ended: bool,
 }
 
 impl ParserCheckpoint {
+    /// The position in the file that the track reader has to be opened at to resume from this checkpoint.
     pub fn reader_pos(&self) -> u64 {
         self.reader_pos
     }
+
+    /// The absolute time in ticks of the last event parsed before this checkpoint.
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+
+    pub fn is_ended(&self) -> bool {
+        self.ended
+    }
 }
 
 impl<T: TrackReader> TrackParser<T> {
+    /// Resume parsing from a checkpoint, where the reader has to be opened at [`ParserCheckpoint::reader_pos`].
     pub fn from_checkpoint(reader: T, checkpoint: ParserCheckpoint) -> Self {
+        Self::from_checkpoint_with_options(reader, checkpoint, ParseOptions::default())
+    }
+
+    pub fn from_checkpoint_with_options(
+        reader: T,
+        checkpoint: ParserCheckpoint,
+        options: ParseOptions,
+    ) -> Self {
         assert_eq!(
             checkpoint.reader_pos,
             reader.pos(),
             "Checkpoint reader pos does not match reader pos"
         );
 
+        // A pushed back byte is always the last byte that was read before the checkpoint
+        let pushback_pos = reader.pos().saturating_sub(1);
+
         Self {
             reader,
             pushback: checkpoint.pushback,
+            pushback_pos,
             prev_command: checkpoint.prev_command,
             errored: checkpoint.ended,
+            carried_delta: 0,
+            sysex_open: checkpoint.sysex_open,
+            time: checkpoint.time,
+            options,
         }
     }
 
+    /// Capture the current state of the parser, to resume parsing from this point later.
+    pub fn checkpoint(&self) -> ParserCheckpoint {
+        ParserCheckpoint {
+            pushback: self.pushback,
+            prev_command: self.prev_command,
+            sysex_open: self.sysex_open,
+            reader_pos: self.reader.pos(),
+            time: self.time,
+            ended: self.errored,
+        }
+    }
+
+    /// The absolute time in ticks of the last parsed event.
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+
     pub fn new(reader: T) -> Self {
+        Self::new_with_options(reader, ParseOptions::default())
+    }
+
+    pub fn new_with_options(reader: T, options: ParseOptions) -> Self {
         Self {
             reader,
             pushback: -1,
+            pushback_pos: 0,
             prev_command: 0,
             errored: false,
+            carried_delta: 0,
+            sysex_open: false,
+            time: 0,
+            options,
         }
     }
 
+    pub fn options(&self) -> &ParseOptions {
+        &self.options
+    }
+
+    pub fn set_options(&mut self, options: ParseOptions) {
+        self.options = options;
+    }
+
     fn read(&mut self) -> Result<u8, MIDIParseError> {
         if self.pushback != -1 {
             let p: u8 = self.pushback as u8;
@@ -60,6 +203,55 @@ impl<T: TrackReader> TrackParser<T> {
         self.reader.read()
     }
 
+    /// Push back the byte that was just read, so that the next [`read`](#method.read) returns it again.
+    fn push_back(&mut self, byte: u8) {
+        self.pushback = byte as i16;
+        self.pushback_pos = self.reader.pos() - 1;
+    }
+
+    /// The position in the file of the byte that the next [`read`](#method.read) returns.
+    #[inline(always)]
+    fn next_byte_pos(&self) -> u64 {
+        if self.pushback != -1 {
+            self.pushback_pos
+        } else {
+            self.reader.pos()
+        }
+    }
+
+    /// Check a data byte of a channel event, clamping it if the options allow.
+    #[inline(always)]
+    fn check_data(&self, value: u8, position: u64) -> u8 {
+        if value > 0x7F && self.options.clamp_data_bytes {
+            self.options.warn(ParseWarning::ClampedDataByte {
+                track_number: self.reader.track_number(),
+                position,
+                value,
+            });
+            0x7F
+        } else {
+            value
+        }
+    }
+
+    fn skip_bytes(&mut self, count: u64) -> Result<(), MIDIParseError> {
+        for _ in 0..count {
+            self.read_fast()?;
+        }
+        Ok(())
+    }
+
+    /// Reads the length and data of an `F0` or `F7` event.
+    fn read_sysex_packet(&mut self) -> Result<Vec<u8>, MIDIParseError> {
+        let size = self.read_var_length()?;
+        let mut data = Vec::with_capacity(size.min(0x10000) as usize);
+        for _ in 0..size {
+            data.push(self.read_fast()?);
+        }
+        data.shrink_to_fit();
+        Ok(data)
+    }
+
     fn read_var_length(&mut self) -> Result<u64, MIDIParseError> {
         let mut n: u64 = 0;
         loop {
@@ -79,76 +271,117 @@ impl<T: TrackReader> TrackParser<T> {
             };
         }
 
+        let delta = self.read_var_length()? + std::mem::take(&mut self.carried_delta);
+
         macro_rules! assert_len {
             ($size:expr) => {
-                if self.read_fast()? != $size {
-                    return Err(MIDIParseError::CorruptEvent {
+                let len = self.read_fast()?;
+                let position = self.reader.pos();
+                if len != $size {
+                    if !self.options.skip_corrupt_events {
+                        return Err(MIDIParseError::CorruptEvent {
+                            track_number: self.reader.track_number(),
+                            position,
+                        });
+                    }
+
+                    // The length is a variable length value, so it may continue into the next bytes
+                    let mut len = len as u64;
+                    if len & 0x80 != 0 {
+                        self.push_back(len as u8);
+                        len = self.read_var_length()?;
+                    }
+                    self.skip_bytes(len)?;
+
+                    self.options.warn(ParseWarning::SkippedCorruptEvent {
                         track_number: self.reader.track_number(),
-                        position: self.reader.pos(),
+                        position,
                     });
+                    self.carried_delta = delta;
+                    return Ok(None);
                 }
             };
         }
 
-        let delta = self.read_var_length()?;
+        macro_rules! data {
+            ($val:expr) => {{
+                let position = self.next_byte_pos();
+                let val = $val;
+                self.check_data(val, position)
+            }};
+        }
+
+        let mut command_pos = self.next_byte_pos();
         let mut command = self.read()?;
+        while command < 0x80 && self.prev_command < 0x80 && self.options.skip_stray_bytes {
+            self.options.warn(ParseWarning::SkippedByte {
+                track_number: self.reader.track_number(),
+                position: command_pos,
+                byte: command,
+            });
+            command_pos = self.next_byte_pos();
+            command = self.read()?;
+        }
         if command < 0x80 {
-            self.pushback = command as i16;
+            self.push_back(command);
             command = self.prev_command;
         }
-        self.prev_command = command;
+        // Realtime messages can be interleaved with other messages, so they don't affect running status
+        if !(0xF8..=0xFE).contains(&command) {
+            self.prev_command = command;
+        }
         let comm = command & 0xF0;
         match comm {
             0x80 => {
                 let channel = command & 0x0F;
-                let key = self.read()?;
-                let _vel = self.read_fast()?;
-                ret!(Event::new_delta_note_off_event(delta, channel, key))
+                let key = data!(self.read()?);
+                let vel = data!(self.read_fast()?);
+                ret!(Event::new_delta_note_off_event(delta, channel, key, vel))
             }
             0x90 => {
                 let channel = command & 0x0F;
-                let key = self.read()?;
-                let vel = self.read_fast()?;
+                let key = data!(self.read()?);
+                let vel = data!(self.read_fast()?);
                 if vel == 0 {
-                    ret!(Event::new_delta_note_off_event(delta, channel, key))
+                    ret!(Event::new_delta_note_off_event(delta, channel, key, 0))
                 } else {
                     ret!(Event::new_delta_note_on_event(delta, channel, key, vel))
                 }
             }
             0xA0 => {
                 let channel = command & 0x0F;
-                let key = self.read()?;
-                let vel = self.read_fast()?;
+                let key = data!(self.read()?);
+                let vel = data!(self.read_fast()?);
                 ret!(Event::new_delta_polyphonic_key_pressure_event(
                     delta, channel, key, vel
                 ))
             }
             0xB0 => {
                 let channel = command & 0x0F;
-                let controller = self.read()?;
-                let value = self.read_fast()?;
+                let controller = data!(self.read()?);
+                let value = data!(self.read_fast()?);
                 ret!(Event::new_delta_control_change_event(
                     delta, channel, controller, value
                 ))
             }
             0xC0 => {
                 let channel = command & 0x0F;
-                let program = self.read()?;
+                let program = data!(self.read()?);
                 ret!(Event::new_delta_program_change_event(
                     delta, channel, program
                 ))
             }
             0xD0 => {
                 let channel = command & 0x0F;
-                let pressure = self.read()?;
+                let pressure = data!(self.read()?);
                 ret!(Event::new_delta_channel_pressure_event(
                     delta, channel, pressure
                 ))
             }
             0xE0 => {
                 let channel = command & 0x0F;
-                let var1 = self.read()?;
-                let var2 = self.read_fast()?;
+                let var1 = data!(self.read()?);
+                let var2 = data!(self.read_fast()?);
                 ret!(Event::new_delta_pitch_wheel_change_event(
                     delta,
                     channel,
@@ -157,14 +390,18 @@ impl<T: TrackReader> TrackParser<T> {
             }
             _ => match command {
                 0xF0 => {
-                    let size = self.read_var_length()?;
-                    let mut data = Vec::new();
-                    for _ in 0..size {
-                        data.push(self.read_fast()?);
-                    }
-                    data.shrink_to_fit();
+                    let data = self.read_sysex_packet()?;
+                    self.sysex_open = data.last() != Some(&0xF7);
                     ret!(Event::new_delta_system_exclusive_message_event(delta, data))
                 }
+                0xF1 => {
+                    let data = self.read()?;
+                    ret!(Event::new_delta_mtc_quarter_frame_event(
+                        delta,
+                        (data >> 4) & 0x07,
+                        data & 0x0F
+                    ))
+                }
                 0xF2 => {
                     let var1 = self.read()?;
                     let var2 = self.read_fast()?;
@@ -181,10 +418,32 @@ impl<T: TrackReader> TrackParser<T> {
                     ret!(Event::new_delta_tune_request_event(delta))
                 }
                 0xF7 => {
-                    ret!(Event::new_delta_end_of_exclusive_event(delta))
+                    let data = self.read_sysex_packet()?;
+                    if self.sysex_open {
+                        self.sysex_open = data.last() != Some(&0xF7);
+                        ret!(Event::new_delta_system_exclusive_continuation_event(
+                            delta, data
+                        ))
+                    } else if let Some(event) = Event::from_system_message(&data) {
+                        ret!(Delta::new(delta, event))
+                    } else {
+                        ret!(Event::new_delta_escape_event(delta, data))
+                    }
                 }
                 0xF8 => {
-                    ret!(Event::new_delta_end_of_exclusive_event(delta))
+                    ret!(Event::new_delta_timing_clock_event(delta))
+                }
+                0xFA => {
+                    ret!(Event::new_delta_start_event(delta))
+                }
+                0xFB => {
+                    ret!(Event::new_delta_continue_event(delta))
+                }
+                0xFC => {
+                    ret!(Event::new_delta_stop_event(delta))
+                }
+                0xFE => {
+                    ret!(Event::new_delta_active_sensing_event(delta))
                 }
                 0xFF => {
                     let command = self.read()?;
@@ -201,6 +460,12 @@ impl<T: TrackReader> TrackParser<T> {
                             }
                             data.shrink_to_fit();
 
+                            if command == 0x0A {
+                                if let Some(color) = ColorEvent::from_meta_data(&data) {
+                                    return ret!(Delta::new(delta, color.as_event()));
+                                }
+                            }
+
                             ret!(Event::new_delta_text_event(
                                 delta,
                                 TextEventKind::from_val(command),
@@ -284,12 +549,30 @@ impl<T: TrackReader> Iterator for TrackParser<T> {
 
             let next_event = self.try_parse_next_event();
             match next_event {
-                Ok(Some(event)) => return Some(Ok(event)),
+                Ok(Some(event)) => {
+                    self.time += event.delta;
+                    return Some(Ok(event));
+                }
                 Ok(None) => {
                     // We skip some events such as track end events,
                     // so we just loop again to get the next one
                     continue;
                 }
+                Err(MIDIParseError::UnexpectedTrackEnd {
+                    track_number,
+                    track_start,
+                    expected_track_end,
+                    found_track_end,
+                }) if self.options.allow_truncated_tracks => {
+                    self.errored = true;
+                    self.options.warn(ParseWarning::TruncatedTrack {
+                        track_number,
+                        track_start,
+                        expected_track_end,
+                        found_track_end,
+                    });
+                    return None;
+                }
                 Err(e) => {
                     self.errored = true;
                     return Some(Err(e));
@@ -298,3 +581,253 @@ impl<T: TrackReader> Iterator for TrackParser<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{
+        events::{Event, SerializeEventWithDelta},
+        io::{FullRamTrackReader, MIDIParseError, ParseOptions, ParseWarning, TrackParser},
+        sequence::event::Delta,
+    };
+
+    type ParseResult = Vec<Result<Delta<u64, Event>, MIDIParseError>>;
+
+    fn parse(bytes: Vec<u8>, options: ParseOptions) -> (ParseResult, Vec<ParseWarning>) {
+        let warnings = Arc::new(Mutex::new(Vec::new()));
+        let warnings_clone = warnings.clone();
+        let options =
+            options.with_warning_callback(move |w| warnings_clone.lock().unwrap().push(w));
+
+        let reader = FullRamTrackReader::new_from_vec(Some(0), bytes);
+        let events = TrackParser::new_with_options(reader, options).collect();
+
+        let warnings = warnings.lock().unwrap().clone();
+        (events, warnings)
+    }
+
+    fn unwrap_all(events: ParseResult) -> Vec<Delta<u64, Event>> {
+        events.into_iter().map(|e| e.unwrap()).collect()
+    }
+
+    #[test]
+    fn strict_truncated_track_errors() {
+        let bytes = vec![0x10, 0x90, 0x40, 0x7F, 0x10, 0x80, 0x40];
+        let (events, warnings) = parse(bytes, ParseOptions::strict());
+
+        assert_eq!(events.len(), 2);
+        assert!(matches!(
+            events[1],
+            Err(MIDIParseError::UnexpectedTrackEnd { .. })
+        ));
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn lenient_truncated_track_ends() {
+        let bytes = vec![0x10, 0x90, 0x40, 0x7F, 0x10, 0x80, 0x40];
+        let (events, warnings) = parse(bytes, ParseOptions::lenient());
+
+        assert_eq!(
+            unwrap_all(events),
+            vec![Event::new_delta_note_on_event(0x10, 0, 0x40, 0x7F)]
+        );
+        assert_eq!(
+            warnings,
+            vec![ParseWarning::TruncatedTrack {
+                track_number: Some(0),
+                track_start: 0,
+                expected_track_end: 7,
+                found_track_end: 7,
+            }]
+        );
+    }
+
+    #[test]
+    fn lenient_skips_stray_bytes() {
+        let bytes = vec![0x10, 0x12, 0x90, 0x40, 0x7F, 0x10, 0x40, 0x00];
+        let (events, warnings) = parse(bytes, ParseOptions::lenient());
+
+        assert_eq!(
+            unwrap_all(events),
+            vec![
+                Event::new_delta_note_on_event(0x10, 0, 0x40, 0x7F),
+                Event::new_delta_note_off_event(0x10, 0, 0x40, 0),
+            ]
+        );
+        assert_eq!(
+            warnings,
+            vec![ParseWarning::SkippedByte {
+                track_number: Some(0),
+                position: 1,
+                byte: 0x12,
+            }]
+        );
+    }
+
+    #[test]
+    fn lenient_skips_corrupt_meta_events() {
+        let bytes = vec![
+            0x10, 0xFF, 0x51, 0x02, 0x07, 0xA1, // Tempo event with a length of 2
+            0x10, 0x90, 0x40, 0x7F,
+        ];
+
+        let (events, _) = parse(bytes.clone(), ParseOptions::strict());
+        assert!(matches!(
+            events[0],
+            Err(MIDIParseError::CorruptEvent { .. })
+        ));
+
+        let (events, warnings) = parse(bytes, ParseOptions::lenient());
+        assert_eq!(
+            unwrap_all(events),
+            vec![Event::new_delta_note_on_event(0x20, 0, 0x40, 0x7F)]
+        );
+        assert_eq!(
+            warnings,
+            vec![ParseWarning::SkippedCorruptEvent {
+                track_number: Some(0),
+                position: 4,
+            }]
+        );
+    }
+
+    #[test]
+    fn lenient_clamps_data_bytes() {
+        let bytes = vec![0x10, 0xB0, 0x07, 0x90];
+        let (events, warnings) = parse(bytes, ParseOptions::lenient());
+
+        assert_eq!(
+            unwrap_all(events),
+            vec![Event::new_delta_control_change_event(0x10, 0, 0x07, 0x7F)]
+        );
+        assert_eq!(
+            warnings,
+            vec![ParseWarning::ClampedDataByte {
+                track_number: Some(0),
+                position: 3,
+                value: 0x90,
+            }]
+        );
+    }
+
+    #[test]
+    fn warning_positions_after_running_status() {
+        let bytes = vec![
+            0x00, 0x90, 0x40, 0x7F, // note on
+            0x10, 0x41, 0xC5, // running status, with an out of range velocity
+            0x10, 0x42, 0x7F, // running status
+        ];
+        let (events, warnings) = parse(bytes, ParseOptions::lenient());
+
+        assert_eq!(
+            unwrap_all(events),
+            vec![
+                Event::new_delta_note_on_event(0x00, 0, 0x40, 0x7F),
+                Event::new_delta_note_on_event(0x10, 0, 0x41, 0x7F),
+                Event::new_delta_note_on_event(0x10, 0, 0x42, 0x7F),
+            ]
+        );
+        assert_eq!(
+            warnings,
+            vec![ParseWarning::ClampedDataByte {
+                track_number: Some(0),
+                position: 6,
+                value: 0xC5,
+            }]
+        );
+    }
+
+    #[test]
+    fn sysex_packets_and_escapes() {
+        let bytes = vec![
+            0x00, 0xF0, 0x05, 0x7E, 0x7F, 0x09, 0x01, 0xF7, // complete message
+            0x10, 0xF0, 0x03, 0x43, 0x12, 0x00, // first packet
+            0x20, 0xF7, 0x02, 0x43, 0x12, // continuation
+            0x30, 0xF7, 0x02, 0x00, 0xF7, // last continuation
+            0x40, 0xF7, 0x02, 0xF3, 0x01, // escaped song select
+            0x00, 0xF7, 0x02, 0xF8, 0xF8, // escape
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let (events, _) = parse(bytes.clone(), ParseOptions::strict());
+        let events = unwrap_all(events);
+
+        assert_eq!(
+            events,
+            vec![
+                Event::new_delta_system_exclusive_message_event(
+                    0x00,
+                    vec![0x7E, 0x7F, 0x09, 0x01, 0xF7]
+                ),
+                Event::new_delta_system_exclusive_message_event(0x10, vec![0x43, 0x12, 0x00]),
+                Event::new_delta_system_exclusive_continuation_event(0x20, vec![0x43, 0x12]),
+                Event::new_delta_system_exclusive_continuation_event(0x30, vec![0x00, 0xF7]),
+                Event::new_delta_song_select_event(0x40, 0x01),
+                Event::new_delta_escape_event(0x00, vec![0xF8, 0xF8]),
+            ]
+        );
+
+        let mut written = Vec::new();
+        for event in events.iter() {
+            event.serialize_event_with_delta(&mut written).unwrap();
+        }
+        assert_eq!(written, bytes[..bytes.len() - 4]);
+    }
+
+    #[test]
+    fn note_off_velocity_round_trips() {
+        let bytes = vec![
+            0x00, 0x90, 0x40, 0x7F, // note on
+            0x10, 0x80, 0x40, 0x35, // note off with a release velocity
+            0x00, 0x90, 0x41, 0x7F, // note on
+            0x10, 0x41, 0x00, // note on with velocity 0, using running status
+        ];
+        let (events, _) = parse(bytes, ParseOptions::strict());
+        let events = unwrap_all(events);
+
+        assert_eq!(
+            events,
+            vec![
+                Event::new_delta_note_on_event(0x00, 0, 0x40, 0x7F),
+                Event::new_delta_note_off_event(0x10, 0, 0x40, 0x35),
+                Event::new_delta_note_on_event(0x00, 0, 0x41, 0x7F),
+                Event::new_delta_note_off_event(0x10, 0, 0x41, 0),
+            ]
+        );
+
+        let mut written = Vec::new();
+        for event in events.iter().take(2) {
+            event.serialize_event_with_delta(&mut written).unwrap();
+        }
+        assert_eq!(
+            written,
+            vec![0x00, 0x90, 0x40, 0x7F, 0x10, 0x80, 0x40, 0x35]
+        );
+    }
+
+    #[test]
+    fn note_off_velocity_is_checked() {
+        let bytes = vec![
+            0x00, 0x90, 0x40, 0x7F, // note on
+            0x10, 0x80, 0x40, 0xB5, // note off with an out of range release velocity
+        ];
+        let (events, warnings) = parse(bytes, ParseOptions::lenient());
+
+        assert_eq!(
+            unwrap_all(events),
+            vec![
+                Event::new_delta_note_on_event(0x00, 0, 0x40, 0x7F),
+                Event::new_delta_note_off_event(0x10, 0, 0x40, 0x7F),
+            ]
+        );
+        assert_eq!(
+            warnings,
+            vec![ParseWarning::ClampedDataByte {
+                track_number: Some(0),
+                position: 7,
+                value: 0xB5,
+            }]
+        );
+    }
+}
@@ -28,6 +28,13 @@ pub trait MIDINote<T: MIDINum>: std::fmt::Debug {
         *self.velocity_mut() = velocity;
     }
 
+    /// The velocity of the note off event that ends the note.
+    /// Note types that don't store it always return 0.
+    #[inline(always)]
+    fn release_velocity(&self) -> u8 {
+        0
+    }
+
     #[inline(always)]
     fn end(&self) -> T {
         self.start() + self.len()
@@ -67,6 +74,7 @@ pub struct Note<T: MIDINum> {
     pub key: u8,
     pub channel: u8,
     pub velocity: u8,
+    pub release_velocity: u8,
 }
 
 impl<T: MIDINum> MIDINote<T> for Note<T> {
@@ -119,4 +127,9 @@ impl<T: MIDINum> MIDINote<T> for Note<T> {
     fn velocity_mut(&mut self) -> &mut u8 {
         &mut self.velocity
     }
+
+    #[inline(always)]
+    fn release_velocity(&self) -> u8 {
+        self.release_velocity
+    }
 }
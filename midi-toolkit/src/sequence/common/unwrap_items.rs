@@ -20,7 +20,7 @@ mod tests {
     fn panic() {
         let events = vec![
             Ok(Event::new_delta_note_on_event(100.0f64, 0, 64, 127)),
-            Ok(Event::new_delta_note_off_event(50.0f64, 0, 64)),
+            Ok(Event::new_delta_note_off_event(50.0f64, 0, 64, 0)),
             Err(()),
         ];
 
@@ -35,7 +35,7 @@ mod tests {
     fn no_panic() {
         let events: Vec<Result<_, ()>> = vec![
             Ok(Event::new_delta_note_on_event(100.0f64, 0, 64, 127)),
-            Ok(Event::new_delta_note_off_event(50.0f64, 0, 64)),
+            Ok(Event::new_delta_note_off_event(50.0f64, 0, 64, 0)),
         ];
 
         let changed = pipe! {
@@ -48,7 +48,7 @@ mod tests {
             changed,
             vec![
                 Event::new_delta_note_on_event(100.0f64, 0, 64, 127),
-                Event::new_delta_note_off_event(50.0f64, 0, 64),
+                Event::new_delta_note_off_event(50.0f64, 0, 64, 0),
             ]
         )
     }
@@ -1,4 +1,4 @@
-use std::{cell::Cell, collections::VecDeque, rc::Rc};
+use std::collections::VecDeque;
 
 use crate::gen_iter::GenIter;
 
@@ -6,110 +6,255 @@ use crate::{
     events::{Event, MIDIEventEnum},
     notes::Note,
     num::MIDINum,
-    sequence::event::Delta,
+    sequence::event::{Delta, Track},
     unwrap,
 };
 
+/// How a note off event is matched to the open notes of its key and channel,
+/// when several notes of the same key overlap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NoteMatchPolicy {
+    /// End the oldest open note, the default
+    #[default]
+    Fifo,
+    /// End the newest open note
+    Lifo,
+    /// End every open note
+    EndAll,
+    /// Ignore note on events while a note is already held. The note offs of the ignored
+    /// note ons are skipped once there is no held note left to end.
+    IgnoreRetrigger,
+}
+
+/// A problem found while pairing note on and note off events in [`events_to_notes_with`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum NoteDiagnostic<D: MIDINum> {
+    /// A note off event didn't match any open note, and was ignored
+    UnmatchedNoteOff { time: D, channel: u8, key: u8 },
+    /// A note was still open at the end of the sequence, and was ended at the time of the last event
+    UnendedNote { start: D, channel: u8, key: u8 },
+}
+
+impl<D: MIDINum> std::fmt::Display for NoteDiagnostic<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NoteDiagnostic::UnmatchedNoteOff { time, channel, key } => write!(
+                f,
+                "Unmatched note off (time: {time}, channel: {channel}, key: {key})"
+            ),
+            NoteDiagnostic::UnendedNote {
+                start,
+                channel,
+                key,
+            } => write!(
+                f,
+                "Note was never ended (start: {start}, channel: {channel}, key: {key})"
+            ),
+        }
+    }
+}
+
 // ==============
 // Helper structs
 // ==============
 
 #[derive(Debug)]
 struct UnendedContainer<T: MIDINum> {
-    new_end: Cell<Option<T>>,
+    /// The end time and the release velocity, once the note off event is found
+    new_end: Option<(T, u8)>,
     note: Note<T>,
+    track: u32,
 }
 
 impl<T: MIDINum> UnendedContainer<T> {
-    fn new(note: Note<T>) -> Self {
+    fn new(note: Note<T>, track: u32) -> Self {
         Self {
-            new_end: Cell::new(None),
+            new_end: None,
             note,
+            track,
         }
     }
 }
 
+/// The open notes, in the order of their start. The notes of each key are referenced by their
+/// position in the queue (counted from the first note ever pushed), which keeps the converter `Send`.
+///
+/// Notes of different tracks share the queue of their key, a note off only ends notes of its own track.
+/// The queue of a key is only searched for the notes of a track once notes of several tracks were pushed.
 struct NoteQueue<T: MIDINum> {
-    queue: VecDeque<Rc<UnendedContainer<T>>>,
-    keys: Vec<VecDeque<Rc<UnendedContainer<T>>>>,
+    policy: NoteMatchPolicy,
+    queue: VecDeque<UnendedContainer<T>>,
+    /// The number of notes that have been popped from the front of the queue
+    popped: usize,
+    keys: Vec<VecDeque<usize>>,
+    /// The track of the first note that was pushed
+    first_track: Option<u32>,
+    /// Whether notes of more than one track were pushed
+    multi_track: bool,
+    /// The number of ignored note ons of each track, per key. Only used with [`NoteMatchPolicy::IgnoreRetrigger`]
+    ignored: Vec<Vec<(u32, u32)>>,
 }
 
 impl<T: MIDINum> NoteQueue<T> {
-    fn new() -> Self {
+    fn new(policy: NoteMatchPolicy) -> Self {
         let queue = VecDeque::new();
         let mut keys = Vec::new();
+        let mut ignored = Vec::new();
         for _ in 0..(256 * 16) {
             keys.push(VecDeque::new());
+            ignored.push(Vec::new());
+        }
+
+        Self {
+            policy,
+            queue,
+            popped: 0,
+            keys,
+            first_track: None,
+            multi_track: false,
+            ignored,
+        }
+    }
+
+    #[inline(always)]
+    fn key_index(key: u8, channel: u8) -> usize {
+        key as usize * 16 + channel as usize
+    }
+
+    /// Finds the open notes of the track in the queue of a key, the first one if `last` is false.
+    #[inline(always)]
+    fn find_open(&self, index: usize, track: u32, last: bool) -> Option<usize> {
+        let open = &self.keys[index];
+        if !self.multi_track {
+            if open.is_empty() || self.first_track != Some(track) {
+                return None;
+            }
+            return Some(if last { open.len() - 1 } else { 0 });
         }
 
-        Self { queue, keys }
+        let is_track = |position: &usize| self.queue[position - self.popped].track == track;
+        if last {
+            open.iter().rposition(is_track)
+        } else {
+            open.iter().position(is_track)
+        }
     }
 
     #[inline(always)]
-    fn get_queue(&mut self, key: u8, channel: u8) -> &mut VecDeque<Rc<UnendedContainer<T>>> {
-        &mut self.keys[key as usize * 16 + channel as usize]
+    fn push(&mut self, note: Note<T>, track: u32) {
+        let index = Self::key_index(note.key, note.channel);
+        if self.policy == NoteMatchPolicy::IgnoreRetrigger
+            && self.find_open(index, track, false).is_some()
+        {
+            let ignored = &mut self.ignored[index];
+            match ignored.iter_mut().find(|(t, _)| *t == track) {
+                Some((_, count)) => *count += 1,
+                None => ignored.push((track, 1)),
+            }
+            return;
+        }
+
+        match self.first_track {
+            None => self.first_track = Some(track),
+            Some(first) => self.multi_track |= first != track,
+        }
+
+        self.keys[index].push_back(self.popped + self.queue.len());
+        self.queue.push_back(UnendedContainer::new(note, track));
     }
 
     #[inline(always)]
-    fn push(&mut self, note: Note<T>) {
-        let key = self.get_queue(note.key, note.channel);
-        let note = Rc::new(UnendedContainer::new(note));
-        key.push_back(note.clone());
-        self.queue.push_back(note);
+    fn set_end(&mut self, position: usize, end: T, release_velocity: u8) {
+        self.queue[position - self.popped].new_end = Some((end, release_velocity));
     }
 
+    /// Ends the open notes that the note off matches, returns false if it didn't match anything.
     #[inline(always)]
-    fn end_next(&mut self, key: u8, channel: u8, end: T) {
-        let queue = self.get_queue(key, channel);
-        if let Some(note) = queue.pop_front() {
-            note.new_end.set(Some(end));
+    fn end_next(&mut self, track: u32, key: u8, channel: u8, end: T, release_velocity: u8) -> bool {
+        let index = Self::key_index(key, channel);
+        let found = self.find_open(index, track, self.policy == NoteMatchPolicy::Lifo);
+
+        let ended = match found {
+            Some(i) => self.keys[index].remove(i),
+            None => {
+                if self.policy == NoteMatchPolicy::IgnoreRetrigger {
+                    let ignored = &mut self.ignored[index];
+                    if let Some(i) = ignored.iter().position(|(t, _)| *t == track) {
+                        ignored[i].1 -= 1;
+                        if ignored[i].1 == 0 {
+                            ignored.swap_remove(i);
+                        }
+                        return true;
+                    }
+                }
+                return false;
+            }
+        };
+
+        if let Some(position) = ended {
+            self.set_end(position, end, release_velocity);
+        }
+        if self.policy == NoteMatchPolicy::EndAll {
+            while let Some(i) = self.find_open(index, track, false) {
+                let position = self.keys[index].remove(i).unwrap();
+                self.set_end(position, end, release_velocity);
+            }
         }
+        true
     }
 
     #[inline(always)]
-    fn end_all(&mut self, end: T) {
+    fn end_all(&mut self, end: T, mut on_unended: impl FnMut(&Note<T>)) {
         for key in self.keys.iter_mut() {
-            for note in key.iter_mut() {
-                note.new_end.set(Some(end));
+            key.clear();
+        }
+        for note in self.queue.iter_mut() {
+            if note.new_end.is_none() {
+                on_unended(&note.note);
+                note.new_end = Some((end, 0));
             }
         }
     }
 
     #[inline(always)]
-    fn next_ended_note(&mut self) -> Option<Note<T>> {
+    fn next_ended_note(&mut self) -> Option<Track<Note<T>>> {
         let next = self.queue.front();
         if let Some(next) = next {
-            if let Some(end) = next.new_end.get() {
-                let next_note = self.queue.pop_front().unwrap();
-                let mut note = Rc::try_unwrap(next_note).unwrap().note;
+            if let Some((end, release_velocity)) = next.new_end {
+                let container = self.queue.pop_front().unwrap();
+                self.popped += 1;
+                let mut note = container.note;
                 note.len = end - note.start;
-                return Some(note);
+                note.release_velocity = release_velocity;
+                return Some(Track::new(note, container.track));
             }
         }
         None
     }
 }
 
-/// Takes an event iterator and converts it to a note iterator.
-/// Effectively extracting the notes from an event sequence.
-pub fn events_to_notes<
+fn convert_events_to_notes<D, E, Err, I>(
+    iter: I,
+    policy: NoteMatchPolicy,
+    mut diagnostics: impl FnMut(NoteDiagnostic<D>),
+    track_of: impl Fn(&E) -> u32,
+) -> impl Iterator<Item = Result<Track<Note<D>>, Err>>
+where
     D: MIDINum,
     E: MIDIEventEnum,
-    Err,
     I: Iterator<Item = Result<Delta<D, E>, Err>> + Sized,
->(
-    iter: I,
-) -> impl Iterator<Item = Result<Note<D>, Err>> {
+{
     GenIter(
         #[coroutine]
         move || {
-            let mut note_queue = NoteQueue::<D>::new();
+            let mut note_queue = NoteQueue::<D>::new(policy);
 
             let mut time = D::zero();
             for e in iter {
                 let e = unwrap!(e);
 
                 time += e.delta;
+                let track = track_of(&e.event);
                 match e.as_event() {
                     Event::NoteOn(e) => {
                         let note = Note {
@@ -117,13 +262,20 @@ pub fn events_to_notes<
                             channel: e.channel,
                             key: e.key,
                             velocity: e.velocity,
+                            release_velocity: 0,
                             len: D::zero(),
                         };
 
-                        note_queue.push(note);
+                        note_queue.push(note, track);
                     }
                     Event::NoteOff(e) => {
-                        note_queue.end_next(e.key, e.channel, time);
+                        if !note_queue.end_next(track, e.key, e.channel, time, e.velocity) {
+                            diagnostics(NoteDiagnostic::UnmatchedNoteOff {
+                                time,
+                                channel: e.channel,
+                                key: e.key,
+                            });
+                        }
 
                         while let Some(note) = note_queue.next_ended_note() {
                             yield Ok(note);
@@ -133,7 +285,13 @@ pub fn events_to_notes<
                 }
             }
 
-            note_queue.end_all(time);
+            note_queue.end_all(time, |note| {
+                diagnostics(NoteDiagnostic::UnendedNote {
+                    start: note.start,
+                    channel: note.channel,
+                    key: note.key,
+                })
+            });
             while let Some(note) = note_queue.next_ended_note() {
                 yield Ok(note);
             }
@@ -141,13 +299,117 @@ pub fn events_to_notes<
     )
 }
 
+/// Takes an event iterator and converts it to a note iterator.
+/// Effectively extracting the notes from an event sequence.
+///
+/// Note offs are matched to the oldest open note of the same key and channel, unmatched note offs are ignored,
+/// and notes that are still open at the end are ended at the time of the last event.
+/// Use [`events_to_notes_with`] to change the matching or to find out about the unmatched events.
+pub fn events_to_notes<
+    D: MIDINum,
+    E: MIDIEventEnum,
+    Err,
+    I: Iterator<Item = Result<Delta<D, E>, Err>> + Sized,
+>(
+    iter: I,
+) -> impl Iterator<Item = Result<Note<D>, Err>> {
+    events_to_notes_with(iter, NoteMatchPolicy::Fifo, |_| {})
+}
+
+/// Same as [`events_to_notes`], with a [`NoteMatchPolicy`] for overlapping notes of the same key,
+/// and a callback that receives a [`NoteDiagnostic`] for every unmatched note off and every note
+/// that is still open at the end of the sequence.
+///
+/// ## Example
+///```
+///use midi_toolkit::{
+///    events::Event,
+///    pipe,
+///    sequence::{events_to_notes_with, to_vec_result, wrap_ok, NoteDiagnostic, NoteMatchPolicy},
+///};
+///
+///let events = vec![
+///    Event::new_delta_note_on_event(0u64, 0, 64, 127),
+///    Event::new_delta_note_on_event(10, 0, 64, 100),
+///    Event::new_delta_note_off_event(10, 0, 64, 0),
+///    Event::new_delta_note_off_event(10, 0, 65, 0),
+///];
+///
+///let mut diagnostics = Vec::new();
+///let notes = pipe! {
+///    events.into_iter()
+///    |>wrap_ok()
+///    |>events_to_notes_with(NoteMatchPolicy::Lifo, |d| diagnostics.push(d))
+///    |>to_vec_result().unwrap()
+///};
+///
+///// The newer note is ended first, the older one is ended at the last event
+///assert_eq!(notes.iter().map(|n| (n.start, n.len)).collect::<Vec<_>>(), vec![(0, 30), (10, 10)]);
+///assert_eq!(
+///    diagnostics,
+///    vec![
+///        NoteDiagnostic::UnmatchedNoteOff { time: 30, channel: 0, key: 65 },
+///        NoteDiagnostic::UnendedNote { start: 0, channel: 0, key: 64 },
+///    ]
+///);
+///```
+pub fn events_to_notes_with<
+    D: MIDINum,
+    E: MIDIEventEnum,
+    Err,
+    I: Iterator<Item = Result<Delta<D, E>, Err>> + Sized,
+>(
+    iter: I,
+    policy: NoteMatchPolicy,
+    diagnostics: impl FnMut(NoteDiagnostic<D>),
+) -> impl Iterator<Item = Result<Note<D>, Err>> {
+    convert_events_to_notes(iter, policy, diagnostics, |_| 0).map(|n| n.map(Track::inner_event))
+}
+
+/// Same as [`events_to_notes`], for events with a track index, e.g. from
+/// [`MIDIFile::iter_all_track_events_merged`](crate::io::MIDIFile::iter_all_track_events_merged).
+/// Each note keeps the track of its note on, and note offs only end notes of their own track.
+/// Use [`events_to_track_notes_with`] to change the matching or to find out about the unmatched events.
+pub fn events_to_track_notes<
+    D: MIDINum,
+    E: MIDIEventEnum,
+    Err,
+    I: Iterator<Item = Result<Delta<D, Track<E>>, Err>> + Sized,
+>(
+    iter: I,
+) -> impl Iterator<Item = Result<Track<Note<D>>, Err>> {
+    events_to_track_notes_with(iter, NoteMatchPolicy::Fifo, |_| {})
+}
+
+/// Same as [`events_to_track_notes`], with a [`NoteMatchPolicy`] and a callback for diagnostics
+/// like [`events_to_notes_with`]. The policy is applied to the notes of each track separately.
+pub fn events_to_track_notes_with<
+    D: MIDINum,
+    E: MIDIEventEnum,
+    Err,
+    I: Iterator<Item = Result<Delta<D, Track<E>>, Err>> + Sized,
+>(
+    iter: I,
+    policy: NoteMatchPolicy,
+    diagnostics: impl FnMut(NoteDiagnostic<D>),
+) -> impl Iterator<Item = Result<Track<Note<D>>, Err>> {
+    convert_events_to_notes(iter, policy, diagnostics, |e| e.track)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
         events::Event,
         notes::Note,
         pipe,
-        sequence::{conversion::events_to_notes, to_vec_result, wrap_ok},
+        sequence::{
+            conversion::{
+                events_to_notes, events_to_notes_with, events_to_track_notes,
+                events_to_track_notes_with, NoteDiagnostic, NoteMatchPolicy,
+            },
+            event::{into_track_events, merge_events},
+            to_vec_result, wrap_ok,
+        },
     };
 
     #[test]
@@ -156,10 +418,10 @@ mod tests {
             Event::new_delta_note_on_event(100.0f64, 0, 64, 127),
             Event::new_delta_note_on_event(30.0f64, 0, 64, 127),
             Event::new_delta_tempo_event(25.0f64, 0),
-            Event::new_delta_note_off_event(25.0f64, 0, 64),
-            Event::new_delta_note_off_event(80.0f64, 0, 64),
+            Event::new_delta_note_off_event(25.0f64, 0, 64, 40),
+            Event::new_delta_note_off_event(80.0f64, 0, 64, 50),
             Event::new_delta_note_on_event(0.0f64, 1, 64, 127),
-            Event::new_delta_note_off_event(80.0f64, 1, 64),
+            Event::new_delta_note_off_event(80.0f64, 1, 64, 0),
         ];
 
         let changed = pipe! {
@@ -175,6 +437,7 @@ mod tests {
                 channel: 0,
                 key: 64,
                 velocity: 127,
+                release_velocity: 40,
                 len: 80.0,
             },
             Note {
@@ -182,6 +445,7 @@ mod tests {
                 channel: 0,
                 key: 64,
                 velocity: 127,
+                release_velocity: 50,
                 len: 130.0,
             },
             Note {
@@ -189,10 +453,160 @@ mod tests {
                 channel: 1,
                 key: 64,
                 velocity: 127,
+                release_velocity: 0,
                 len: 80.0,
             },
         ];
 
         assert_eq!(changed, expected);
     }
+
+    #[test]
+    fn match_policies() {
+        let events = vec![
+            Event::new_delta_note_on_event(0u64, 0, 64, 127),
+            Event::new_delta_note_on_event(10, 0, 64, 100),
+            Event::new_delta_note_off_event(10, 0, 64, 0),
+            Event::new_delta_note_off_event(10, 0, 64, 0),
+            Event::new_delta_note_off_event(10, 0, 64, 0),
+        ];
+
+        let unmatched = |time| NoteDiagnostic::UnmatchedNoteOff {
+            time,
+            channel: 0,
+            key: 64,
+        };
+
+        let cases = [
+            (NoteMatchPolicy::Fifo, vec![(0, 20), (10, 20)], vec![40]),
+            (NoteMatchPolicy::Lifo, vec![(0, 30), (10, 10)], vec![40]),
+            (
+                NoteMatchPolicy::EndAll,
+                vec![(0, 20), (10, 10)],
+                vec![30, 40],
+            ),
+            (NoteMatchPolicy::IgnoreRetrigger, vec![(0, 20)], vec![40]),
+        ];
+
+        for (policy, expected_notes, expected_unmatched) in cases {
+            let mut diagnostics = Vec::new();
+            let notes = pipe! {
+                events.clone().into_iter()
+                |>wrap_ok()
+                |>events_to_notes_with(policy, |d| diagnostics.push(d))
+                |>to_vec_result().unwrap()
+            };
+
+            let notes = notes.iter().map(|n| (n.start, n.len)).collect::<Vec<_>>();
+            assert_eq!(notes, expected_notes, "{policy:?}");
+            let expected_unmatched = expected_unmatched
+                .into_iter()
+                .map(unmatched)
+                .collect::<Vec<_>>();
+            assert_eq!(diagnostics, expected_unmatched, "{policy:?}");
+        }
+    }
+
+    #[test]
+    fn track_notes_only_end_their_own_notes() {
+        let track0 = vec![
+            Event::new_delta_note_on_event(0u64, 0, 64, 127),
+            Event::new_delta_note_off_event(30, 0, 64, 0),
+        ];
+        let track1 = vec![
+            Event::new_delta_note_on_event(10u64, 0, 64, 100),
+            Event::new_delta_note_off_event(5, 0, 64, 0),
+        ];
+
+        let track0 = pipe!(track0.into_iter()|>wrap_ok()|>into_track_events(0));
+        let track1 = pipe!(track1.into_iter()|>wrap_ok()|>into_track_events(1));
+        let merged = merge_events(track0, track1);
+        let notes = pipe! {
+            merged
+            |>events_to_track_notes()
+            |>to_vec_result().unwrap()
+        };
+
+        let notes = notes
+            .iter()
+            .map(|n| (n.track, n.start, n.len, n.velocity))
+            .collect::<Vec<_>>();
+        assert_eq!(notes, vec![(0, 0, 30, 127), (1, 10, 5, 100)]);
+    }
+
+    #[test]
+    fn track_notes_with_policy() {
+        let track0 = vec![
+            Event::new_delta_note_on_event(0u64, 0, 64, 127),
+            Event::new_delta_note_on_event(10, 0, 64, 100),
+            Event::new_delta_note_off_event(20, 0, 64, 0),
+        ];
+        let track1 = vec![
+            Event::new_delta_note_on_event(5u64, 0, 64, 90),
+            Event::new_delta_note_off_event(20, 0, 64, 0),
+            Event::new_delta_note_off_event(10, 0, 64, 0),
+        ];
+
+        let track0 = pipe!(track0.into_iter()|>wrap_ok()|>into_track_events(0));
+        let track1 = pipe!(track1.into_iter()|>wrap_ok()|>into_track_events(1));
+        let merged = merge_events(track0, track1);
+        let mut diagnostics = Vec::new();
+        let notes = pipe! {
+            merged
+            |>events_to_track_notes_with(NoteMatchPolicy::Lifo, |d| diagnostics.push(d))
+            |>to_vec_result().unwrap()
+        };
+
+        let notes = notes
+            .iter()
+            .map(|n| (n.track, n.start, n.len))
+            .collect::<Vec<_>>();
+        assert_eq!(notes, vec![(0, 0, 35), (1, 5, 20), (0, 10, 20)]);
+        assert_eq!(
+            diagnostics,
+            vec![
+                NoteDiagnostic::UnmatchedNoteOff {
+                    time: 35,
+                    channel: 0,
+                    key: 64
+                },
+                NoteDiagnostic::UnendedNote {
+                    start: 0,
+                    channel: 0,
+                    key: 64
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn track_notes_ignore_retrigger() {
+        let track0 = vec![
+            Event::new_delta_note_on_event(0u64, 0, 64, 127),
+            Event::new_delta_note_on_event(5, 0, 64, 100),
+            Event::new_delta_note_off_event(5, 0, 64, 0),
+            Event::new_delta_note_off_event(2, 0, 64, 0),
+        ];
+        let track1 = vec![
+            Event::new_delta_note_on_event(3u64, 0, 64, 90),
+            Event::new_delta_note_off_event(17, 0, 64, 0),
+        ];
+
+        let track0 = pipe!(track0.into_iter()|>wrap_ok()|>into_track_events(0));
+        let track1 = pipe!(track1.into_iter()|>wrap_ok()|>into_track_events(1));
+        let merged = merge_events(track0, track1);
+        let mut diagnostics = Vec::new();
+        let notes = pipe! {
+            merged
+            |>events_to_track_notes_with(NoteMatchPolicy::IgnoreRetrigger, |d| diagnostics.push(d))
+            |>to_vec_result().unwrap()
+        };
+
+        let notes = notes
+            .iter()
+            .map(|n| (n.track, n.start, n.len))
+            .collect::<Vec<_>>();
+        assert_eq!(notes, vec![(0, 0, 10), (1, 3, 17)]);
+        assert!(diagnostics.is_empty());
+    }
 }
@@ -6,31 +6,31 @@ use crate::{
     events::{Event, NoteOffEvent},
     notes::MIDINote,
     num::MIDINum,
-    sequence::event::Delta,
+    sequence::event::{Delta, Track},
     unwrap,
 };
 
 /// A temporary struct for ordering note off events in a binary heap.
-struct NoteOffHolder<D: MIDINum>(Delta<D, NoteOffEvent>);
+struct NoteOffHolder<D: MIDINum, E>(Delta<D, E>);
 
-impl<D: MIDINum> NoteOffHolder<D> {
-    fn new(delta: D, event: NoteOffEvent) -> Self {
+impl<D: MIDINum, E> NoteOffHolder<D, E> {
+    fn new(delta: D, event: E) -> Self {
         Self(Delta::new(delta, event))
     }
 
-    fn into_event(self) -> Delta<D, Event> {
-        Delta::new(self.0.delta, Event::NoteOff(self.0.event))
+    fn into_event(self) -> Delta<D, E> {
+        self.0
     }
 }
 
-impl<D: MIDINum> PartialEq for NoteOffHolder<D> {
+impl<D: MIDINum, E> PartialEq for NoteOffHolder<D, E> {
     fn eq(&self, other: &Self) -> bool {
         self.0.delta == other.0.delta
     }
 }
-impl<D: MIDINum> Eq for NoteOffHolder<D> {}
+impl<D: MIDINum, E> Eq for NoteOffHolder<D, E> {}
 
-impl<D: MIDINum> Ord for NoteOffHolder<D> {
+impl<D: MIDINum, E> Ord for NoteOffHolder<D, E> {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         self.0
             .delta
@@ -40,21 +40,21 @@ impl<D: MIDINum> Ord for NoteOffHolder<D> {
     }
 }
 
-impl<D: MIDINum> PartialOrd for NoteOffHolder<D> {
+impl<D: MIDINum, E> PartialOrd for NoteOffHolder<D, E> {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         Some(self.cmp(other))
     }
 }
 
-/// Takes a note iterator and converts it to a note event iterator.
-/// Effectively flattening the notes into an event sequence.
-pub fn notes_to_events<D: MIDINum, N: MIDINote<D>, Err>(
+/// Converts the notes into events, `wrap` turns each event of a note into the output event type.
+fn convert_notes_to_events<D: MIDINum, N: MIDINote<D>, Err, E>(
     iter: impl Iterator<Item = Result<N, Err>> + Sized,
-) -> impl Iterator<Item = Result<Delta<D, Event>, Err>> {
+    wrap: impl Fn(&N, Event) -> E,
+) -> impl Iterator<Item = Result<Delta<D, E>, Err>> {
     GenIter(
         #[coroutine]
         move || {
-            let mut note_offs = BinaryHeap::<NoteOffHolder<D>>::new();
+            let mut note_offs = BinaryHeap::<NoteOffHolder<D, E>>::new();
 
             let mut prev_time = D::zero();
 
@@ -74,18 +74,14 @@ pub fn notes_to_events<D: MIDINum, N: MIDINote<D>, Err>(
                     }
                 }
 
-                yield Ok(Event::new_delta_note_on_event(
-                    note.start() - prev_time,
-                    note.channel(),
-                    note.key(),
-                    note.velocity(),
-                ));
+                let on = Event::new_note_on_event(note.channel(), note.key(), note.velocity());
+                yield Ok(Delta::new(note.start() - prev_time, wrap(&note, on)));
 
                 prev_time = note.start();
 
                 let time = note.end();
-                let off = NoteOffEvent::new(note.channel(), note.key());
-                let holder = NoteOffHolder::new(time, off);
+                let off = NoteOffEvent::new(note.channel(), note.key(), note.release_velocity());
+                let holder = NoteOffHolder::new(time, wrap(&note, Event::NoteOff(off)));
 
                 note_offs.push(holder);
             }
@@ -101,13 +97,33 @@ pub fn notes_to_events<D: MIDINum, N: MIDINote<D>, Err>(
     )
 }
 
+/// Takes a note iterator and converts it to a note event iterator.
+/// Effectively flattening the notes into an event sequence.
+pub fn notes_to_events<D: MIDINum, N: MIDINote<D>, Err>(
+    iter: impl Iterator<Item = Result<N, Err>> + Sized,
+) -> impl Iterator<Item = Result<Delta<D, Event>, Err>> {
+    convert_notes_to_events(iter, |_, e| e)
+}
+
+/// Same as [`notes_to_events`], for notes with a track index. The events get the track of their note.
+pub fn track_notes_to_events<D: MIDINum, N: MIDINote<D>, Err>(
+    iter: impl Iterator<Item = Result<Track<N>, Err>> + Sized,
+) -> impl Iterator<Item = Result<Delta<D, Track<Event>>, Err>> {
+    convert_notes_to_events(iter, |note, e| Track::new(e, note.track))
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
         events::Event,
         notes::Note,
         pipe,
-        sequence::{conversion::notes_to_events, to_vec_result, wrap_ok},
+        sequence::{
+            conversion::{events_to_track_notes, notes_to_events, track_notes_to_events},
+            event::into_track_events,
+            note::merge_notes_array,
+            to_vec_result, wrap_ok,
+        },
     };
 
     #[test]
@@ -118,6 +134,7 @@ mod tests {
                 channel: 0,
                 key: 64,
                 velocity: 127,
+                release_velocity: 40,
                 len: 80.0,
             },
             Note {
@@ -125,6 +142,7 @@ mod tests {
                 channel: 0,
                 key: 64,
                 velocity: 127,
+                release_velocity: 50,
                 len: 130.0,
             },
             Note {
@@ -132,6 +150,7 @@ mod tests {
                 channel: 1,
                 key: 64,
                 velocity: 127,
+                release_velocity: 0,
                 len: 80.0,
             },
         ];
@@ -146,12 +165,54 @@ mod tests {
         let expected = vec![
             Event::new_delta_note_on_event(100.0f64, 0, 64, 127),
             Event::new_delta_note_on_event(30.0f64, 0, 64, 127),
-            Event::new_delta_note_off_event(50.0f64, 0, 64),
-            Event::new_delta_note_off_event(80.0f64, 0, 64),
+            Event::new_delta_note_off_event(50.0f64, 0, 64, 40),
+            Event::new_delta_note_off_event(80.0f64, 0, 64, 50),
             Event::new_delta_note_on_event(0.0f64, 1, 64, 127),
-            Event::new_delta_note_off_event(80.0f64, 1, 64),
+            Event::new_delta_note_off_event(80.0f64, 1, 64, 0),
         ];
 
         assert_eq!(changed, expected);
     }
+
+    #[test]
+    fn track_notes_round_trip() {
+        let tracks = vec![
+            vec![
+                Event::new_delta_note_on_event(0u64, 0, 64, 127),
+                Event::new_delta_note_off_event(20, 0, 64, 0),
+            ],
+            vec![
+                Event::new_delta_note_on_event(10u64, 0, 64, 100),
+                Event::new_delta_note_off_event(20, 0, 64, 0),
+            ],
+        ];
+
+        let notes = tracks
+            .into_iter()
+            .enumerate()
+            .map(|(i, track)| {
+                pipe!(track.into_iter()|>wrap_ok()|>into_track_events(i as u32)|>events_to_track_notes())
+            })
+            .collect();
+
+        let changed = pipe! {
+            notes
+            |>merge_notes_array()
+            |>track_notes_to_events()
+            |>to_vec_result().unwrap()
+        };
+
+        let expected = [
+            (0, 0, Event::new_note_on_event(0, 64, 127)),
+            (10, 1, Event::new_note_on_event(0, 64, 100)),
+            (10, 0, Event::new_note_off_event(0, 64, 0)),
+            (10, 1, Event::new_note_off_event(0, 64, 0)),
+        ];
+        assert_eq!(changed.len(), expected.len());
+        for (e, (delta, track, event)) in changed.iter().zip(expected.iter()) {
+            assert_eq!(e.delta, *delta);
+            assert_eq!(e.track, *track);
+            assert_eq!(&e.event.event, event);
+        }
+    }
 }
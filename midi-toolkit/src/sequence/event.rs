@@ -8,6 +8,8 @@ mod cancel_tempo_events;
 pub use cancel_tempo_events::*;
 mod filter_events;
 pub use filter_events::*;
+mod join_sysex_packets;
+pub use join_sysex_packets::*;
 mod stats;
 pub use stats::*;
 mod batched;
@@ -16,3 +18,19 @@ mod delta;
 pub use delta::*;
 mod track;
 pub use track::*;
+mod timed;
+pub use timed::*;
+mod chase_state;
+pub use chase_state::*;
+mod slice_events;
+pub use slice_events::*;
+mod tempo_map;
+pub use tempo_map::*;
+mod meter_map;
+pub use meter_map::*;
+mod to_seconds;
+pub use to_seconds::*;
+mod sample_frames;
+pub use sample_frames::*;
+mod quantize_deltas;
+pub use quantize_deltas::*;
@@ -14,7 +14,7 @@ pub struct EventBatch<T> {
 }
 
 impl<T> EventBatch<T> {
-    fn new(events: Vec<T>) -> Self {
+    pub(crate) fn new(events: Vec<T>) -> Self {
         Self { events }
     }
 
@@ -29,6 +29,10 @@ impl<T> EventBatch<T> {
     pub fn count(&self) -> usize {
         self.events.len()
     }
+
+    pub(crate) fn retain_mut(&mut self, f: impl FnMut(&mut T) -> bool) {
+        self.events.retain_mut(f);
+    }
 }
 
 impl<D: MIDINum, T> Delta<D, EventBatch<T>> {
@@ -95,7 +99,7 @@ impl<E: MIDIEventEnum> BatchTempo for EventBatch<E> {
         let new = self
             .events
             .into_iter()
-            .filter(|e| e.as_event().inner_tempo().is_some())
+            .filter(|e| e.as_event().inner_tempo().is_none())
             .collect::<Vec<_>>();
 
         if new.is_empty() {
@@ -166,3 +170,34 @@ pub fn flatten_track_batches_to_events<D: MIDINum, E: MIDIEvent, Err>(
         },
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        events::{BatchTempo, Event},
+        sequence::event::EventBatch,
+    };
+
+    #[test]
+    fn batch_without_tempo_keeps_other_events() {
+        let batch = EventBatch::new(vec![
+            Event::new_note_on_event(0, 64, 127),
+            Event::new_tempo_event(250000),
+            Event::new_note_on_event(0, 65, 100),
+        ]);
+        assert_eq!(batch.inner_tempo(), Some(250000));
+
+        let batch = batch.without_tempo().unwrap();
+        assert_eq!(batch.inner_tempo(), None);
+        assert_eq!(
+            batch.into_iter_inner().collect::<Vec<_>>(),
+            vec![
+                Event::new_note_on_event(0, 64, 127),
+                Event::new_note_on_event(0, 65, 100),
+            ]
+        );
+
+        let tempos = EventBatch::new(vec![Event::new_tempo_event(250000)]);
+        assert!(tempos.without_tempo().is_none());
+    }
+}
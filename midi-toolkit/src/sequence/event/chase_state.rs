@@ -0,0 +1,686 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::gen_iter::GenIter;
+
+use crate::{
+    events::{Event, MIDIDelta},
+    num::MIDINum,
+    unwrap,
+};
+
+use super::{Delta, EventBatch, Track};
+
+/// A registered (RPN) or non-registered (NRPN) parameter number, selected with controllers 101/100 or 99/98.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ParameterNumber {
+    Registered { msb: u8, lsb: u8 },
+    NonRegistered { msb: u8, lsb: u8 },
+}
+
+impl ParameterNumber {
+    fn select_events(&self, channel: u8) -> [Event; 2] {
+        let (msb_controller, lsb_controller, msb, lsb) = match *self {
+            ParameterNumber::Registered { msb, lsb } => (101, 100, msb, lsb),
+            ParameterNumber::NonRegistered { msb, lsb } => (99, 98, msb, lsb),
+        };
+        [
+            Event::new_control_change_event(channel, msb_controller, msb),
+            Event::new_control_change_event(channel, lsb_controller, lsb),
+        ]
+    }
+}
+
+/// The state of a single channel, as set by the events played on it so far.
+///
+/// Values are `None` if they were never set (or were reset), so that restoring the state
+/// doesn't send values that the sequence never used.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelState {
+    pub program: Option<u8>,
+    /// The last value of every controller, except for the parameter (RPN/NRPN) and channel mode controllers
+    pub controllers: [Option<u8>; 128],
+    pub pitch_bend: Option<i16>,
+    pub channel_pressure: Option<u8>,
+    /// The data entry values (MSB and LSB) of every parameter that was set
+    pub parameters: BTreeMap<ParameterNumber, (u8, Option<u8>)>,
+    pub selected_parameter: Option<ParameterNumber>,
+    /// The velocities of the notes that are currently held down on every key, oldest first.
+    /// Overlapping notes of the same key are all kept, a note off releases the oldest one.
+    pub held_notes: [VecDeque<u8>; 128],
+}
+
+impl Default for ChannelState {
+    fn default() -> Self {
+        Self {
+            program: None,
+            controllers: [None; 128],
+            pitch_bend: None,
+            channel_pressure: None,
+            parameters: BTreeMap::new(),
+            selected_parameter: None,
+            held_notes: std::array::from_fn(|_| VecDeque::new()),
+        }
+    }
+}
+
+fn is_parameter_controller(controller: u8) -> bool {
+    matches!(controller, 6 | 38 | 96..=101)
+}
+
+fn is_channel_mode_controller(controller: u8) -> bool {
+    controller >= 120
+}
+
+/// Controllers that are kept by a reset all controllers message, as recommended by RP-015
+fn is_kept_on_reset(controller: u8) -> bool {
+    matches!(controller, 0 | 7 | 8 | 10 | 32 | 70..=79 | 91..=95)
+}
+
+impl ChannelState {
+    fn control_change(&mut self, controller: u8, value: u8) {
+        match controller {
+            101 | 100 => {
+                let (mut msb, mut lsb) = match self.selected_parameter {
+                    Some(ParameterNumber::Registered { msb, lsb }) => (msb, lsb),
+                    _ => (0, 0),
+                };
+                if controller == 101 {
+                    msb = value;
+                } else {
+                    lsb = value;
+                }
+                self.selected_parameter = if msb == 0x7F && lsb == 0x7F {
+                    None
+                } else {
+                    Some(ParameterNumber::Registered { msb, lsb })
+                };
+            }
+            99 | 98 => {
+                let (mut msb, mut lsb) = match self.selected_parameter {
+                    Some(ParameterNumber::NonRegistered { msb, lsb }) => (msb, lsb),
+                    _ => (0, 0),
+                };
+                if controller == 99 {
+                    msb = value;
+                } else {
+                    lsb = value;
+                }
+                self.selected_parameter = Some(ParameterNumber::NonRegistered { msb, lsb });
+            }
+            6 => {
+                if let Some(parameter) = self.selected_parameter {
+                    self.parameters.insert(parameter, (value, None));
+                }
+            }
+            38 => {
+                if let Some(parameter) = self.selected_parameter {
+                    let entry = self.parameters.entry(parameter).or_insert((0, None));
+                    entry.1 = Some(value);
+                }
+            }
+            121 => {
+                for (controller, value) in self.controllers.iter_mut().enumerate() {
+                    if !is_kept_on_reset(controller as u8) {
+                        *value = None;
+                    }
+                }
+                self.pitch_bend = None;
+                self.channel_pressure = None;
+                self.selected_parameter = None;
+            }
+            120 | 123..=127 => {
+                for notes in self.held_notes.iter_mut() {
+                    notes.clear();
+                }
+            }
+            _ => {
+                if !is_parameter_controller(controller) && !is_channel_mode_controller(controller) {
+                    self.controllers[controller as usize] = Some(value);
+                }
+            }
+        }
+    }
+
+    /// Creates the events that bring a channel from its initial state to this state.
+    ///
+    /// Bank selects come before the program change, and the parameters are set before the
+    /// currently selected parameter is restored.
+    pub fn restore_events(&self, channel: u8, include_held_notes: bool) -> Vec<Event> {
+        let mut events = Vec::new();
+
+        for bank_controller in [0, 32] {
+            if let Some(value) = self.controllers[bank_controller as usize] {
+                events.push(Event::new_control_change_event(
+                    channel,
+                    bank_controller,
+                    value,
+                ));
+            }
+        }
+        if let Some(program) = self.program {
+            events.push(Event::new_program_change_event(channel, program));
+        }
+
+        for (parameter, (msb, lsb)) in self.parameters.iter() {
+            events.extend(parameter.select_events(channel));
+            events.push(Event::new_control_change_event(channel, 6, *msb));
+            if let Some(lsb) = lsb {
+                events.push(Event::new_control_change_event(channel, 38, *lsb));
+            }
+        }
+        match self.selected_parameter {
+            Some(parameter) => events.extend(parameter.select_events(channel)),
+            None if !self.parameters.is_empty() => {
+                events.push(Event::new_control_change_event(channel, 101, 0x7F));
+                events.push(Event::new_control_change_event(channel, 100, 0x7F));
+            }
+            None => {}
+        }
+
+        for (controller, value) in self.controllers.iter().enumerate() {
+            if controller == 0 || controller == 32 {
+                continue;
+            }
+            if let Some(value) = value {
+                events.push(Event::new_control_change_event(
+                    channel,
+                    controller as u8,
+                    *value,
+                ));
+            }
+        }
+
+        if let Some(pitch) = self.pitch_bend {
+            events.push(Event::new_pitch_wheel_change_event(channel, pitch));
+        }
+        if let Some(pressure) = self.channel_pressure {
+            events.push(Event::new_channel_pressure_event(channel, pressure));
+        }
+
+        if include_held_notes {
+            for (key, velocities) in self.held_notes.iter().enumerate() {
+                for velocity in velocities.iter() {
+                    events.push(Event::new_note_on_event(channel, key as u8, *velocity));
+                }
+            }
+        }
+
+        events
+    }
+}
+
+/// The state of a whole sequence at some point in time, built by folding over its events.
+///
+/// ## Example
+///```
+///use midi_toolkit::{events::Event, sequence::event::SequenceState};
+///
+///let mut state = SequenceState::new();
+///state.update(&Event::new_program_change_event(0, 20));
+///state.update(&Event::new_tempo_event(400000));
+///
+///assert_eq!(
+///    state.restore_events(false),
+///    vec![
+///        Event::new_tempo_event(400000),
+///        Event::new_program_change_event(0, 20),
+///    ]
+///);
+///```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SequenceState {
+    pub channels: [ChannelState; 16],
+    pub tempo: Option<u32>,
+}
+
+impl SequenceState {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Applies a single event to the state.
+    pub fn update(&mut self, event: &Event) {
+        match event {
+            Event::NoteOn(e) => self.channels[e.channel as usize & 0x0F].held_notes
+                [e.key as usize & 0x7F]
+                .push_back(e.velocity),
+            Event::NoteOff(e) => {
+                self.channels[e.channel as usize & 0x0F].held_notes[e.key as usize & 0x7F]
+                    .pop_front();
+            }
+            Event::ControlChange(e) => {
+                self.channels[e.channel as usize & 0x0F].control_change(e.controller, e.value)
+            }
+            Event::ProgramChange(e) => {
+                self.channels[e.channel as usize & 0x0F].program = Some(e.program)
+            }
+            Event::ChannelPressure(e) => {
+                self.channels[e.channel as usize & 0x0F].channel_pressure = Some(e.pressure)
+            }
+            Event::PitchWheelChange(e) => {
+                self.channels[e.channel as usize & 0x0F].pitch_bend = Some(e.pitch)
+            }
+            Event::Tempo(e) => self.tempo = Some(e.tempo),
+            Event::SystemReset(_) => *self = SequenceState::new(),
+            _ => {}
+        }
+    }
+
+    /// Creates the events that bring a sequence from its initial state to this state,
+    /// starting with the tempo and followed by the state of every channel in order.
+    pub fn restore_events(&self, include_held_notes: bool) -> Vec<Event> {
+        let mut events = Vec::new();
+        if let Some(tempo) = self.tempo {
+            events.push(Event::new_tempo_event(tempo));
+        }
+        for (channel, state) in self.channels.iter().enumerate() {
+            events.append(&mut state.restore_events(channel as u8, include_held_notes));
+        }
+        events
+    }
+}
+
+/// An event type that can be folded into a [`SequenceState`], and that the state can be restored into.
+pub trait StatefulEvent: Sized {
+    /// Which state the event belongs to. Events with different keys are chased separately,
+    /// so that the events of each track are restored into the same track.
+    fn state_key(&self) -> u32 {
+        0
+    }
+
+    fn update_state(&self, state: &mut SequenceState);
+
+    /// Applies the event to the state of its key, or the state of each inner event's key for batches.
+    fn update_states(&self, states: &mut BTreeMap<u32, SequenceState>) {
+        self.update_state(states.entry(self.state_key()).or_default());
+    }
+
+    /// Removes the events that `keep` returns false for, which is called with the state key of each event.
+    /// Returns false if nothing is left of the event, e.g. an empty batch.
+    fn retain_events(&mut self, keep: &mut dyn FnMut(u32, &Event) -> bool) -> bool;
+
+    /// Wraps the events created by [`SequenceState::restore_events`] into this type, with zero deltas.
+    /// The key is the [`state_key`](StatefulEvent::state_key) of the events the state was built from.
+    fn from_restore_events(events: Vec<Event>, key: u32) -> Vec<Self>;
+}
+
+impl StatefulEvent for Event {
+    fn update_state(&self, state: &mut SequenceState) {
+        state.update(self);
+    }
+
+    fn retain_events(&mut self, keep: &mut dyn FnMut(u32, &Event) -> bool) -> bool {
+        keep(0, self)
+    }
+
+    fn from_restore_events(events: Vec<Event>, _key: u32) -> Vec<Self> {
+        events
+    }
+}
+
+impl<D: MIDINum, E: StatefulEvent> StatefulEvent for Delta<D, E> {
+    fn state_key(&self) -> u32 {
+        self.event.state_key()
+    }
+
+    fn update_state(&self, state: &mut SequenceState) {
+        self.event.update_state(state);
+    }
+
+    fn update_states(&self, states: &mut BTreeMap<u32, SequenceState>) {
+        self.event.update_states(states);
+    }
+
+    fn retain_events(&mut self, keep: &mut dyn FnMut(u32, &Event) -> bool) -> bool {
+        self.event.retain_events(keep)
+    }
+
+    fn from_restore_events(events: Vec<Event>, key: u32) -> Vec<Self> {
+        E::from_restore_events(events, key)
+            .into_iter()
+            .map(|e| Delta::new(D::zero(), e))
+            .collect()
+    }
+}
+
+impl<E: StatefulEvent> StatefulEvent for Track<E> {
+    fn state_key(&self) -> u32 {
+        self.track
+    }
+
+    fn update_state(&self, state: &mut SequenceState) {
+        self.event.update_state(state);
+    }
+
+    fn retain_events(&mut self, keep: &mut dyn FnMut(u32, &Event) -> bool) -> bool {
+        let track = self.track;
+        self.event.retain_events(&mut |_, e| keep(track, e))
+    }
+
+    fn from_restore_events(events: Vec<Event>, key: u32) -> Vec<Self> {
+        E::from_restore_events(events, key)
+            .into_iter()
+            .map(|e| Track::new(e, key))
+            .collect()
+    }
+}
+
+/// A batch can contain the events of several keys (e.g. merged [`Track`] events),
+/// so every inner event is applied to the state of its own key.
+impl<E: StatefulEvent> StatefulEvent for EventBatch<E> {
+    /// The key of the first event in the batch.
+    fn state_key(&self) -> u32 {
+        self.iter_inner().next().map(|e| e.state_key()).unwrap_or(0)
+    }
+
+    fn update_state(&self, state: &mut SequenceState) {
+        for e in self.iter_inner() {
+            e.update_state(state);
+        }
+    }
+
+    fn update_states(&self, states: &mut BTreeMap<u32, SequenceState>) {
+        for e in self.iter_inner() {
+            e.update_states(states);
+        }
+    }
+
+    fn retain_events(&mut self, keep: &mut dyn FnMut(u32, &Event) -> bool) -> bool {
+        self.retain_mut(|e| e.retain_events(keep));
+        self.count() > 0
+    }
+
+    fn from_restore_events(events: Vec<Event>, key: u32) -> Vec<Self> {
+        if events.is_empty() {
+            Vec::new()
+        } else {
+            vec![EventBatch::new(E::from_restore_events(events, key))]
+        }
+    }
+}
+
+/// The chased states of a stream, one for every [`StatefulEvent::state_key`].
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ChasedStates {
+    pub(crate) states: BTreeMap<u32, SequenceState>,
+}
+
+impl ChasedStates {
+    pub(crate) fn update<E: StatefulEvent>(&mut self, event: &E) {
+        event.update_states(&mut self.states);
+    }
+
+    pub(crate) fn restore_events<E: StatefulEvent>(&self, include_held_notes: bool) -> Vec<E> {
+        let mut events = Vec::new();
+        for (key, state) in self.states.iter() {
+            events.extend(E::from_restore_events(
+                state.restore_events(include_held_notes),
+                *key,
+            ));
+        }
+        events
+    }
+}
+
+/// Options for [`chase_state_from`].
+#[derive(Debug, Clone, Default)]
+pub struct ChaseOptions {
+    /// Also send note on events for the notes that are held down at the start tick
+    pub held_notes: bool,
+}
+
+/// Starts a sequence at a tick, replacing the events before it with a burst of events that restores
+/// the state at that tick (tempo, programs, controllers, pitch bends and optionally held notes).
+///
+/// The burst has zero deltas, and the delta of the first event after it is relative to `tick`.
+/// [`Track`] events are chased per track, and each track's state is restored into that track.
+///
+/// ## Example
+///```
+///use midi_toolkit::{
+///    events::Event,
+///    pipe,
+///    sequence::{event::{chase_state_from, ChaseOptions}, to_vec_result, wrap_ok},
+///};
+///
+///let events = vec![
+///    Event::new_delta_program_change_event(0u64, 0, 20),
+///    Event::new_delta_note_on_event(10, 0, 64, 127),
+///    Event::new_delta_note_off_event(100, 0, 64, 0),
+///];
+///
+///let chased = pipe!(
+///    events.into_iter()
+///    |>wrap_ok()
+///    |>chase_state_from(50, ChaseOptions::default())
+///    |>to_vec_result().unwrap()
+///);
+///
+///assert_eq!(
+///    chased,
+///    vec![
+///        Event::new_delta_program_change_event(0, 0, 20),
+///        Event::new_delta_note_off_event(60, 0, 64, 0),
+///    ]
+///);
+///```
+pub fn chase_state_from<D, E, Err, I>(
+    iter: I,
+    tick: D,
+    options: ChaseOptions,
+) -> impl Iterator<Item = Result<E, Err>>
+where
+    D: MIDINum,
+    E: StatefulEvent + MIDIDelta<D>,
+    I: Iterator<Item = Result<E, Err>> + Sized,
+{
+    GenIter(
+        #[coroutine]
+        move || {
+            let mut states = ChasedStates::default();
+            let mut time = D::zero();
+            let mut chased = false;
+
+            for e in iter {
+                let mut e = unwrap!(e);
+                if chased {
+                    yield Ok(e);
+                    continue;
+                }
+
+                time += e.delta();
+                if time < tick {
+                    states.update(&e);
+                    continue;
+                }
+
+                for restore in states.restore_events::<E>(options.held_notes) {
+                    yield Ok(restore);
+                }
+                chased = true;
+
+                e.set_delta(time - tick);
+                yield Ok(e);
+            }
+
+            if !chased {
+                for restore in states.restore_events::<E>(options.held_notes) {
+                    yield Ok(restore);
+                }
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        events::Event,
+        pipe,
+        sequence::{
+            event::{
+                chase_state_from, convert_events_into_batches, flatten_batches_to_events,
+                into_track_events, merge_events_array, ChaseOptions, SequenceState,
+            },
+            to_vec_result, wrap_ok,
+        },
+    };
+
+    #[test]
+    fn restore_channel_state() {
+        let mut state = SequenceState::new();
+        let events = [
+            Event::new_control_change_event(1, 7, 100),
+            Event::new_program_change_event(1, 5),
+            Event::new_control_change_event(1, 0, 1),
+            Event::new_control_change_event(1, 32, 2),
+            // Pitch bend range of 12 semitones
+            Event::new_control_change_event(1, 101, 0),
+            Event::new_control_change_event(1, 100, 0),
+            Event::new_control_change_event(1, 6, 12),
+            Event::new_control_change_event(1, 38, 0),
+            Event::new_control_change_event(1, 101, 0x7F),
+            Event::new_control_change_event(1, 100, 0x7F),
+            Event::new_control_change_event(1, 1, 50),
+            Event::new_pitch_wheel_change_event(1, 1000),
+            Event::new_note_on_event(1, 60, 90),
+            Event::new_note_on_event(1, 62, 80),
+            Event::new_note_off_event(1, 60, 0),
+        ];
+        for event in events.iter() {
+            state.update(event);
+        }
+
+        assert_eq!(
+            state.restore_events(true),
+            vec![
+                Event::new_control_change_event(1, 0, 1),
+                Event::new_control_change_event(1, 32, 2),
+                Event::new_program_change_event(1, 5),
+                Event::new_control_change_event(1, 101, 0),
+                Event::new_control_change_event(1, 100, 0),
+                Event::new_control_change_event(1, 6, 12),
+                Event::new_control_change_event(1, 38, 0),
+                Event::new_control_change_event(1, 101, 0x7F),
+                Event::new_control_change_event(1, 100, 0x7F),
+                Event::new_control_change_event(1, 1, 50),
+                Event::new_control_change_event(1, 7, 100),
+                Event::new_pitch_wheel_change_event(1, 1000),
+                Event::new_note_on_event(1, 62, 80),
+            ]
+        );
+
+        // Reset all controllers keeps the volume and bank, but not the modulation or pitch bend
+        state.update(&Event::new_control_change_event(1, 121, 0));
+        assert_eq!(
+            state.restore_events(false),
+            vec![
+                Event::new_control_change_event(1, 0, 1),
+                Event::new_control_change_event(1, 32, 2),
+                Event::new_program_change_event(1, 5),
+                Event::new_control_change_event(1, 101, 0),
+                Event::new_control_change_event(1, 100, 0),
+                Event::new_control_change_event(1, 6, 12),
+                Event::new_control_change_event(1, 38, 0),
+                Event::new_control_change_event(1, 101, 0x7F),
+                Event::new_control_change_event(1, 100, 0x7F),
+                Event::new_control_change_event(1, 7, 100),
+            ]
+        );
+    }
+
+    #[test]
+    fn chase_batches() {
+        let events = vec![
+            Event::new_delta_tempo_event(0u64, 400000),
+            Event::new_delta_control_change_event(0, 0, 7, 80),
+            Event::new_delta_note_on_event(10, 0, 64, 127),
+            Event::new_delta_control_change_event(10, 0, 7, 90),
+            Event::new_delta_note_off_event(10, 0, 64, 0),
+            Event::new_delta_note_on_event(10, 0, 65, 127),
+        ];
+
+        let chased = pipe!(
+            events.into_iter()
+            |>wrap_ok()
+            |>convert_events_into_batches()
+            |>chase_state_from(20, ChaseOptions { held_notes: true })
+            |>flatten_batches_to_events()
+            |>to_vec_result().unwrap()
+        );
+
+        assert_eq!(
+            chased,
+            vec![
+                Event::new_delta_tempo_event(0, 400000),
+                Event::new_delta_control_change_event(0, 0, 7, 80),
+                Event::new_delta_note_on_event(0, 0, 64, 127),
+                Event::new_delta_control_change_event(0, 0, 7, 90),
+                Event::new_delta_note_off_event(10, 0, 64, 0),
+                Event::new_delta_note_on_event(10, 0, 65, 127),
+            ]
+        );
+    }
+
+    #[test]
+    fn chase_stacked_notes() {
+        let events = vec![
+            Event::new_delta_note_on_event(0u64, 0, 64, 100),
+            Event::new_delta_note_on_event(5, 0, 64, 110),
+            Event::new_delta_note_on_event(5, 0, 64, 120),
+            Event::new_delta_note_off_event(5, 0, 64, 0),
+            Event::new_delta_note_off_event(10, 0, 64, 0),
+            Event::new_delta_note_off_event(10, 0, 64, 0),
+        ];
+
+        let chased = pipe!(
+            events.into_iter()
+            |>wrap_ok()
+            |>chase_state_from(20, ChaseOptions { held_notes: true })
+            |>to_vec_result().unwrap()
+        );
+
+        // The first note was released, the other two are still held
+        assert_eq!(
+            chased,
+            vec![
+                Event::new_delta_note_on_event(0, 0, 64, 110),
+                Event::new_delta_note_on_event(0, 0, 64, 120),
+                Event::new_delta_note_off_event(5, 0, 64, 0),
+                Event::new_delta_note_off_event(10, 0, 64, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn chase_batches_of_several_tracks() {
+        let track0 = vec![Event::new_delta_program_change_event(0u64, 0, 20)];
+        let track1 = vec![Event::new_delta_program_change_event(0u64, 0, 30)];
+
+        let tracks = vec![track0, track1]
+            .into_iter()
+            .enumerate()
+            .map(|(i, track)| into_track_events(track.into_iter().map(Ok::<_, ()>), i as u32))
+            .collect();
+
+        let chased = pipe!(
+            tracks
+            |>merge_events_array()
+            |>convert_events_into_batches()
+            |>chase_state_from(10, ChaseOptions::default())
+            |>flatten_batches_to_events()
+            |>to_vec_result().unwrap()
+        );
+
+        let expected = [
+            (0, Event::new_program_change_event(0, 20)),
+            (1, Event::new_program_change_event(0, 30)),
+        ];
+        assert_eq!(chased.len(), expected.len());
+        for (e, (track, event)) in chased.iter().zip(expected.iter()) {
+            assert_eq!(e.delta, 0);
+            assert_eq!(e.track, *track);
+            assert_eq!(&e.event.event, event);
+        }
+    }
+}
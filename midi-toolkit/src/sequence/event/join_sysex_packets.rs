@@ -0,0 +1,117 @@
+use crate::gen_iter::GenIter;
+
+use crate::{
+    events::{Event, MIDIDelta, MIDIEventEnum},
+    num::MIDINum,
+    unwrap,
+};
+
+/// Joins system exclusive messages that were split into an `F0` packet and `F7` continuation packets
+/// into a single [`SystemExclusiveMessageEvent`](crate::events::SystemExclusiveMessageEvent).
+///
+/// The joined message is placed at the time of its last packet, when the message is complete.
+/// Other events in between the packets are passed through, and the delta of the removed packets
+/// is carried over. A message that is never terminated is yielded at the end of the sequence.
+pub fn join_sysex_packets<D, E, Err, I>(iter: I) -> impl Iterator<Item = Result<E, Err>>
+where
+    D: MIDINum,
+    E: MIDIEventEnum + MIDIDelta<D>,
+    I: Iterator<Item = Result<E, Err>> + Sized,
+{
+    GenIter(
+        #[coroutine]
+        move || {
+            let mut extra_delta = D::zero();
+            let mut open_sysex: Option<E> = None;
+
+            for e in iter {
+                let mut e = unwrap!(e);
+                extra_delta += e.delta();
+
+                let sysex_complete = match e.as_event() {
+                    Event::SystemExclusiveMessage(sysex) => Some(sysex.is_complete()),
+                    _ => None,
+                };
+                if let Some(complete) = sysex_complete {
+                    // A new message while one is still open means the old one was never terminated
+                    if let Some(mut unterminated) = open_sysex.take() {
+                        unterminated.set_delta(extra_delta);
+                        extra_delta = D::zero();
+                        yield Ok(unterminated);
+                    }
+                    if !complete {
+                        open_sysex = Some(e);
+                        continue;
+                    }
+                }
+
+                let packet_complete = match (e.as_event(), open_sysex.as_mut()) {
+                    (Event::SystemExclusiveContinuation(packet), Some(open)) => {
+                        if let Event::SystemExclusiveMessage(sysex) = open.as_event_mut() {
+                            sysex.data.extend_from_slice(&packet.data);
+                        }
+                        Some(packet.is_complete())
+                    }
+                    _ => None,
+                };
+                if let Some(complete) = packet_complete {
+                    if complete {
+                        let mut joined = open_sysex.take().unwrap();
+                        joined.set_delta(extra_delta);
+                        extra_delta = D::zero();
+                        yield Ok(joined);
+                    }
+                    continue;
+                }
+
+                e.set_delta(extra_delta);
+                extra_delta = D::zero();
+                yield Ok(e);
+            }
+
+            if let Some(mut unterminated) = open_sysex {
+                unterminated.set_delta(extra_delta);
+                yield Ok(unterminated);
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        events::Event,
+        pipe,
+        sequence::{event::join_sysex_packets, to_vec_result, wrap_ok},
+    };
+
+    #[test]
+    fn join_split_messages() {
+        let events = vec![
+            Event::new_delta_system_exclusive_message_event(10u64, vec![0x43, 0x12, 0x00]),
+            Event::new_delta_note_on_event(5, 0, 64, 127),
+            Event::new_delta_system_exclusive_continuation_event(5, vec![0x43, 0x12]),
+            Event::new_delta_system_exclusive_continuation_event(10, vec![0x00, 0xF7]),
+            Event::new_delta_escape_event(10, vec![0xF3, 0x01]),
+            Event::new_delta_system_exclusive_message_event(10, vec![0x7E, 0xF7]),
+            Event::new_delta_system_exclusive_message_event(10, vec![0x01]),
+        ];
+
+        let joined =
+            pipe!(events.into_iter()|>wrap_ok()|>join_sysex_packets()|>to_vec_result().unwrap());
+
+        assert_eq!(
+            joined,
+            vec![
+                Event::new_delta_note_on_event(15, 0, 64, 127),
+                Event::new_delta_system_exclusive_message_event(
+                    15,
+                    vec![0x43, 0x12, 0x00, 0x43, 0x12, 0x00, 0xF7]
+                ),
+                Event::new_delta_escape_event(10, vec![0xF3, 0x01]),
+                Event::new_delta_system_exclusive_message_event(10, vec![0x7E, 0xF7]),
+                Event::new_delta_system_exclusive_message_event(10, vec![0x01]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,240 @@
+use crate::{
+    events::{Event, MIDIDelta, MIDIEventEnum, TimeSignatureEvent},
+    num::MIDINum,
+};
+
+/// A position in bars and beats. All the values are zero based, so add one to the bar and beat for display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BarBeatTick {
+    pub bar: u64,
+    /// The beat in the bar, in units of the time signature denominator
+    pub beat: u64,
+    /// The ticks since the start of the beat
+    pub tick: u64,
+}
+
+/// A point where the time signature changes.
+///
+/// A time signature always starts a new bar. If it appears in the middle of a bar,
+/// the part of the bar before it is counted as a whole (shorter) bar.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeterChange {
+    pub tick: u64,
+    /// The index of the bar that starts at `tick`
+    pub bar: u64,
+    pub time_signature: TimeSignatureEvent,
+    pub ticks_per_beat: u64,
+    pub ticks_per_bar: u64,
+}
+
+/// The time signature changes of a sequence, for converting between ticks and bar/beat/tick positions.
+///
+/// ## Example
+///```
+///use midi_toolkit::{
+///    events::Event,
+///    sequence::event::{BarBeatTick, MeterMap},
+///};
+///
+///let events = vec![
+///    Event::new_delta_time_signature_event(0u64, 3, 2, 24, 8),
+///    Event::new_delta_time_signature_event(576, 6, 3, 36, 8),
+///];
+///let map = MeterMap::from_events(events.into_iter().map(Ok::<_, ()>), 96).unwrap();
+///
+///// Two bars of 3/4, followed by 6/8
+///assert_eq!(map.ticks_to_position(100), BarBeatTick { bar: 0, beat: 1, tick: 4 });
+///assert_eq!(map.ticks_to_position(576 + 48 * 7), BarBeatTick { bar: 3, beat: 1, tick: 0 });
+///assert_eq!(map.bars(0, 1000).collect::<Vec<_>>(), vec![0, 288, 576, 864]);
+///```
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeterMap {
+    ppq: u16,
+    /// Sorted by tick, always starts at tick 0 (with 4/4 if the sequence doesn't set a time signature there)
+    changes: Vec<MeterChange>,
+}
+
+impl MeterMap {
+    /// Creates a map with only the default time signature of 4/4.
+    pub fn new(ppq: u16) -> Self {
+        let mut map = MeterMap {
+            ppq,
+            changes: Vec::new(),
+        };
+        map.push(0, TimeSignatureEvent::new(4, 2, 24, 8));
+        map
+    }
+
+    /// Builds a map from time signatures at absolute ticks, which must be sorted.
+    /// If several time signatures are at the same tick, the last one is used.
+    pub fn from_absolute_time_signatures(
+        time_signatures: impl IntoIterator<Item = (u64, TimeSignatureEvent)>,
+        ppq: u16,
+    ) -> Self {
+        let mut map = MeterMap::new(ppq);
+        for (tick, time_signature) in time_signatures {
+            map.push(tick, time_signature);
+        }
+        map
+    }
+
+    /// Builds a map from the time signature events of a sequence, which should contain the events of
+    /// every track (e.g. [`MIDIFile::iter_all_events_merged`](crate::io::MIDIFile::iter_all_events_merged)).
+    ///
+    /// Fractional ticks are rounded down.
+    pub fn from_events<D: MIDINum, E: MIDIEventEnum + MIDIDelta<D>, Err>(
+        iter: impl Iterator<Item = Result<E, Err>>,
+        ppq: u16,
+    ) -> Result<Self, Err> {
+        let mut map = MeterMap::new(ppq);
+        let mut time = D::zero();
+        for e in iter {
+            let e = e?;
+            time += e.delta();
+            if let Event::TimeSignature(time_signature) = e.as_event() {
+                map.push(time.midi_num_into(), (**time_signature).clone());
+            }
+        }
+        Ok(map)
+    }
+
+    fn push(&mut self, tick: u64, time_signature: TimeSignatureEvent) {
+        let bar = match self.changes.last() {
+            None => 0,
+            Some(last) => {
+                if tick <= last.tick {
+                    let bar = last.bar;
+                    self.changes.pop();
+                    bar
+                } else {
+                    last.bar + (tick - last.tick).div_ceil(last.ticks_per_bar)
+                }
+            }
+        };
+
+        self.changes.push(MeterChange {
+            tick,
+            bar,
+            ticks_per_beat: time_signature.ticks_per_beat(self.ppq),
+            ticks_per_bar: time_signature.ticks_per_bar(self.ppq),
+            time_signature,
+        });
+    }
+
+    pub fn ppq(&self) -> u16 {
+        self.ppq
+    }
+
+    /// All the time signature changes, starting with the one at tick 0.
+    pub fn changes(&self) -> &[MeterChange] {
+        &self.changes
+    }
+
+    fn change_index_at(&self, tick: u64) -> usize {
+        self.changes
+            .partition_point(|c| c.tick <= tick)
+            .saturating_sub(1)
+    }
+
+    /// The time signature that is active at the tick.
+    pub fn meter_at(&self, tick: u64) -> &MeterChange {
+        &self.changes[self.change_index_at(tick)]
+    }
+
+    /// Converts an absolute tick into a bar/beat/tick position.
+    pub fn ticks_to_position(&self, tick: u64) -> BarBeatTick {
+        let change = self.meter_at(tick);
+        let offset = tick - change.tick;
+        let in_bar = offset % change.ticks_per_bar;
+        BarBeatTick {
+            bar: change.bar + offset / change.ticks_per_bar,
+            beat: in_bar / change.ticks_per_beat,
+            tick: in_bar % change.ticks_per_beat,
+        }
+    }
+
+    /// Converts a bar/beat/tick position into an absolute tick. Beats and ticks that overflow
+    /// the bar or beat are carried into the following ones.
+    pub fn position_to_ticks(&self, position: BarBeatTick) -> u64 {
+        let index = self
+            .changes
+            .partition_point(|c| c.bar <= position.bar)
+            .saturating_sub(1);
+        let change = &self.changes[index];
+        change.tick
+            + (position.bar - change.bar) * change.ticks_per_bar
+            + position.beat * change.ticks_per_beat
+            + position.tick
+    }
+
+    fn boundaries(
+        &self,
+        start: u64,
+        end: u64,
+        step: fn(&MeterChange) -> u64,
+    ) -> impl Iterator<Item = u64> + '_ {
+        let first = self.change_index_at(start);
+        self.changes[first..]
+            .iter()
+            .enumerate()
+            .take_while(move |(_, change)| change.tick < end)
+            .flat_map(move |(i, change)| {
+                let next = match self.changes.get(first + i + 1) {
+                    Some(next) => next.tick.min(end),
+                    None => end,
+                };
+                let step = step(change);
+                let from = if start > change.tick {
+                    change.tick + (start - change.tick).div_ceil(step) * step
+                } else {
+                    change.tick
+                };
+                (from..next).step_by(step as usize)
+            })
+    }
+
+    /// Iterates the ticks at which bars start, in `[start, end)`.
+    pub fn bars(&self, start: u64, end: u64) -> impl Iterator<Item = u64> + '_ {
+        self.boundaries(start, end, |change| change.ticks_per_bar)
+    }
+
+    /// Iterates the ticks at which beats start, in `[start, end)`.
+    pub fn beats(&self, start: u64, end: u64) -> impl Iterator<Item = u64> + '_ {
+        self.boundaries(start, end, |change| change.ticks_per_beat)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        events::TimeSignatureEvent,
+        sequence::event::{BarBeatTick, MeterMap},
+    };
+
+    #[test]
+    fn meter_map_positions() {
+        // 4/4 by default, then 7/8 in the middle of the second bar
+        let map = MeterMap::from_absolute_time_signatures(
+            vec![(60, TimeSignatureEvent::new(7, 3, 12, 8))],
+            10,
+        );
+
+        assert_eq!(map.changes().len(), 2);
+        assert_eq!(map.changes()[1].bar, 2);
+        assert_eq!(map.changes()[1].ticks_per_bar, 35);
+
+        let position = BarBeatTick {
+            bar: 3,
+            beat: 4,
+            tick: 2,
+        };
+        assert_eq!(map.ticks_to_position(60 + 35 + 22), position);
+        assert_eq!(map.position_to_ticks(position), 60 + 35 + 22);
+        for tick in 0..200 {
+            assert_eq!(map.position_to_ticks(map.ticks_to_position(tick)), tick);
+        }
+
+        assert_eq!(map.bars(30, 140).collect::<Vec<_>>(), vec![40, 60, 95, 130]);
+        assert_eq!(map.beats(45, 75).collect::<Vec<_>>(), vec![50, 60, 65, 70]);
+    }
+}
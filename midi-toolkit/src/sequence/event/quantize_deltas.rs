@@ -0,0 +1,128 @@
+use crate::gen_iter::GenIter;
+
+use crate::{num::MIDINum, unwrap};
+
+use super::{Delta, Rounding};
+
+/// Convert the deltas of a sequence from any time type (e.g. `f64` after
+/// [`scale_event_time`](super::scale_event_time)) into whole `u64` ticks, so that it can be written
+/// with [`TrackWriter::write_events_iter`](crate::io::TrackWriter::write_events_iter).
+///
+/// The absolute time of each event is rounded instead of each delta, so the fractional parts
+/// are carried over and every event stays within one tick of its exact time.
+/// Negative times are clamped, so events are never moved before the previous one.
+///
+/// ## Example
+///```
+///use midi_toolkit::{
+///    events::Event,
+///    pipe,
+///    sequence::{event::{quantize_deltas_to_ticks, Rounding}, to_vec_result, wrap_ok},
+///};
+///
+///let events = vec![
+///    Event::new_delta_note_on_event(0.4f64, 0, 64, 127),
+///    Event::new_delta_note_off_event(0.4, 0, 64, 0),
+///    Event::new_delta_note_on_event(0.4, 0, 64, 127),
+///    Event::new_delta_note_off_event(0.4, 0, 64, 0),
+///];
+///
+///let changed = pipe! {
+///    events.into_iter()
+///    |>wrap_ok()
+///    |>quantize_deltas_to_ticks(Rounding::Nearest)
+///    |>to_vec_result().unwrap()
+///};
+///
+///assert_eq!(
+///    changed,
+///    vec![
+///        Event::new_delta_note_on_event(0u64, 0, 64, 127),
+///        Event::new_delta_note_off_event(1, 0, 64, 0),
+///        Event::new_delta_note_on_event(0, 0, 64, 127),
+///        Event::new_delta_note_off_event(1, 0, 64, 0),
+///    ]
+///)
+///```
+pub fn quantize_deltas_to_ticks<D, E, Err, I>(
+    iter: I,
+    rounding: Rounding,
+) -> impl Iterator<Item = Result<Delta<u64, E>, Err>>
+where
+    D: MIDINum,
+    I: Iterator<Item = Result<Delta<D, E>, Err>> + Sized,
+{
+    GenIter(
+        #[coroutine]
+        move || {
+            let mut time = D::zero();
+            let mut tick_time: u64 = 0;
+            for e in iter {
+                let e = unwrap!(e);
+                time += e.delta;
+                let exact: f64 = time.midi_num_into();
+                let rounded = rounding.round(exact).max(0.0) as u64;
+                let delta = rounded.saturating_sub(tick_time);
+                tick_time += delta;
+                yield Ok(Delta::new(delta, e.event));
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        events::Event,
+        pipe,
+        sequence::{
+            event::{quantize_deltas_to_ticks, Rounding},
+            to_vec_result, wrap_ok,
+        },
+    };
+
+    #[test]
+    fn quantized_times_stay_within_one_tick() {
+        let events = (0..10000)
+            .map(|i| Event::new_delta_note_on_event(0.37f64 + (i % 7) as f64 * 0.11, 0, 64, 127))
+            .collect::<Vec<_>>();
+
+        for rounding in [Rounding::Nearest, Rounding::Floor, Rounding::Ceil] {
+            let quantized = pipe! {
+                events.clone().into_iter()
+                |>wrap_ok()
+                |>quantize_deltas_to_ticks(rounding)
+                |>to_vec_result().unwrap()
+            };
+
+            let mut exact = 0.0;
+            let mut time = 0;
+            for (event, quantized) in events.iter().zip(quantized.iter()) {
+                exact += event.delta;
+                time += quantized.delta;
+                assert!((time as f64 - exact).abs() <= 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn quantize_signed_deltas() {
+        let events = vec![
+            Event::new_delta_note_on_event(5i32, 0, 64, 127),
+            Event::new_delta_note_off_event(-7, 0, 64, 0),
+            Event::new_delta_note_on_event(4, 0, 64, 127),
+        ];
+
+        let quantized = pipe! {
+            events.into_iter()
+            |>wrap_ok()
+            |>quantize_deltas_to_ticks(Rounding::Floor)
+            |>to_vec_result().unwrap()
+        };
+
+        assert_eq!(
+            quantized.iter().map(|e| e.delta).collect::<Vec<_>>(),
+            vec![5, 0, 0]
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use crate::gen_iter::GenIter;
+
+use crate::{events::BatchTempo, io::TimeDivision, unwrap};
+
+use super::Delta;
+
+/// The events that fall into a single audio buffer, created by [`split_into_audio_blocks`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioBlock<E> {
+    /// The absolute frame of the first sample in the block
+    pub start_frame: u64,
+    /// The events in the block, with their frame offset from `start_frame`
+    pub events: Vec<(u32, E)>,
+}
+
+/// Stamp each event of a sequence with the absolute sample frame it happens at, for the given sample rate.
+///
+/// The time is tracked as an exact fraction of a frame, so unlike adding up floating point deltas
+/// the frames don't drift, no matter how long the sequence is. Each frame is rounded down.
+///
+/// The sequence must contain the tempo events of every track, e.g. the merged events of a file.
+/// The tempo events are kept in the output.
+///
+/// ## Example
+///```
+///use midi_toolkit::{events::Event, pipe, sequence::{event::to_sample_frames, to_vec_result, wrap_ok}};
+///
+///let events = vec![
+///    Event::new_delta_note_on_event(96u64, 0, 64, 127),
+///    Event::new_delta_tempo_event(0, 250000),
+///    Event::new_delta_note_off_event(1, 0, 64, 0),
+///];
+///
+///let frames = pipe! {
+///    events.into_iter()
+///    |>wrap_ok()
+///    |>to_sample_frames(96, 44100)
+///    |>to_vec_result().unwrap()
+///};
+///
+///// At 120 bpm and 96 ppq, a tick is 229.6875 frames, and 114.84375 frames at 240 bpm
+///assert_eq!(
+///    frames,
+///    vec![
+///        (22050, Event::new_note_on_event(0, 64, 127)),
+///        (22050, Event::new_tempo_event(250000)),
+///        (22164, Event::new_note_off_event(0, 64, 0)),
+///    ]
+///)
+///```
+pub fn to_sample_frames<E, Err, I>(
+    iter: I,
+    division: impl Into<TimeDivision>,
+    sample_rate: u32,
+) -> impl Iterator<Item = Result<(u64, E), Err>>
+where
+    E: BatchTempo,
+    I: Iterator<Item = Result<Delta<u64, E>, Err>> + Sized,
+{
+    let division = division.into();
+    GenIter(
+        #[coroutine]
+        move || {
+            // The time in frames is `time / denominator`, the denominator doesn't depend on the tempo
+            let (mut tick_length, denominator) = division.tick_seconds_ratio(500000);
+            let denominator = denominator as u128;
+            let mut time: u128 = 0;
+
+            for e in iter {
+                let e = unwrap!(e);
+                time += e.delta as u128 * tick_length as u128 * sample_rate as u128;
+                if let Some(tempo) = e.event.inner_tempo() {
+                    tick_length = division.tick_seconds_ratio(tempo).0;
+                }
+                yield Ok(((time / denominator) as u64, e.event));
+            }
+        },
+    )
+}
+
+/// Group a sequence of events stamped with absolute frames (e.g. from [`to_sample_frames`])
+/// into consecutive audio buffers of `block_size` frames.
+///
+/// Every block is returned, including empty ones, up to the block with the last event.
+pub fn split_into_audio_blocks<E, Err, I>(
+    iter: I,
+    block_size: u32,
+) -> impl Iterator<Item = Result<AudioBlock<E>, Err>>
+where
+    I: Iterator<Item = Result<(u64, E), Err>> + Sized,
+{
+    assert!(block_size > 0, "The block size can't be 0");
+    GenIter(
+        #[coroutine]
+        move || {
+            let block_size = block_size as u64;
+            let mut start_frame = 0;
+            let mut events = Vec::new();
+
+            for e in iter {
+                let (frame, event) = unwrap!(e);
+                while frame >= start_frame + block_size {
+                    yield Ok(AudioBlock {
+                        start_frame,
+                        events: std::mem::take(&mut events),
+                    });
+                    start_frame += block_size;
+                }
+                events.push((frame.saturating_sub(start_frame) as u32, event));
+            }
+
+            if !events.is_empty() {
+                yield Ok(AudioBlock {
+                    start_frame,
+                    events,
+                });
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        events::Event,
+        io::TimeDivision,
+        pipe,
+        sequence::{
+            event::{split_into_audio_blocks, to_sample_frames, AudioBlock, Delta},
+            to_vec_result,
+        },
+    };
+
+    #[test]
+    fn frames_dont_drift() {
+        let iter = (0..1000000)
+            .map(|_| Event::new_delta_note_on_event(1u64, 0, 64, 127))
+            .map(Ok::<_, ()>);
+        let (frame, _) = pipe!(iter|>to_sample_frames(96, 44100))
+            .last()
+            .unwrap()
+            .unwrap();
+        assert_eq!(frame, 229687500);
+
+        // 29.97 fps drop frame, 20.02 frames per tick at 48000 Hz
+        let division = TimeDivision::Smpte {
+            fps: 29,
+            ticks_per_frame: 80,
+        };
+        let iter = (0..100)
+            .map(|_| Event::new_delta_note_on_event(1u64, 0, 64, 127))
+            .map(Ok::<_, ()>);
+        let frames = pipe!(iter|>to_sample_frames(division, 48000)|>to_vec_result().unwrap());
+        assert_eq!(frames[49].0, 1001);
+        assert_eq!(frames[99].0, 2002);
+    }
+
+    #[test]
+    fn split_blocks() {
+        let events: Vec<Delta<u64, Event>> = vec![
+            Event::new_delta_note_on_event(0, 0, 64, 127),
+            Event::new_delta_note_on_event(1, 0, 65, 127),
+            Event::new_delta_note_off_event(2, 0, 64, 0),
+        ];
+        let iter = events.into_iter().map(Ok::<_, ()>);
+        // 250 frames per tick
+        let blocks = pipe!(
+            iter
+            |>to_sample_frames(96, 48000)
+            |>split_into_audio_blocks(200)
+            |>to_vec_result().unwrap()
+        );
+
+        assert_eq!(
+            blocks,
+            vec![
+                AudioBlock {
+                    start_frame: 0,
+                    events: vec![(0, Event::new_note_on_event(0, 64, 127))],
+                },
+                AudioBlock {
+                    start_frame: 200,
+                    events: vec![(50, Event::new_note_on_event(0, 65, 127))],
+                },
+                AudioBlock {
+                    start_frame: 400,
+                    events: vec![],
+                },
+                AudioBlock {
+                    start_frame: 600,
+                    events: vec![(150, Event::new_note_off_event(0, 64, 0))],
+                },
+            ]
+        );
+    }
+}
@@ -1,11 +1,47 @@
+use crate::gen_iter::GenIter;
+
 use crate::{
     events::{MIDIDelta, MIDIEvent},
     num::MIDINum,
+    unwrap,
 };
 
+/// How to round times that don't fall exactly on a tick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rounding {
+    /// Round to the closest tick, halfway times are rounded up
+    #[default]
+    Nearest,
+    Floor,
+    Ceil,
+}
+
+impl Rounding {
+    /// Divides `numerator / denominator` with this rounding.
+    pub(crate) fn divide(self, numerator: u128, denominator: u128) -> u128 {
+        match self {
+            Rounding::Nearest => (numerator + denominator / 2) / denominator,
+            Rounding::Floor => numerator / denominator,
+            Rounding::Ceil => numerator.div_ceil(denominator),
+        }
+    }
+
+    /// Rounds a fractional time to a whole tick with this rounding.
+    pub(crate) fn round(self, time: f64) -> f64 {
+        match self {
+            Rounding::Nearest => (time + 0.5).floor(),
+            Rounding::Floor => time.floor(),
+            Rounding::Ceil => time.ceil(),
+        }
+    }
+}
+
 /// Change the PPQ of an event sequence.
 ///
 /// Similar to [`scale_event_time`](crate::sequence::event::scale_event_time), except does `new_delta = old_delta * to / from`.
+///
+/// Each delta is scaled separately, so with integer deltas the rounding errors add up over time.
+/// Use [`rescale_ppq`] to convert integer deltas without drifting.
 /// ## Example
 ///```
 ///use midi_toolkit::{
@@ -16,9 +52,9 @@ use crate::{
 ///
 ///let events = vec![
 ///    Event::new_delta_note_on_event(100.0f64, 0, 64, 127),
-///    Event::new_delta_note_off_event(50.0f64, 0, 64),
+///    Event::new_delta_note_off_event(50.0f64, 0, 64, 0),
 ///    Event::new_delta_note_on_event(30.0f64, 0, 64, 127),
-///    Event::new_delta_note_off_event(80.0f64, 0, 64),
+///    Event::new_delta_note_off_event(80.0f64, 0, 64, 0),
 ///];
 ///
 ///let changed = pipe! {
@@ -32,9 +68,9 @@ use crate::{
 ///    changed,
 ///    vec![
 ///        Event::new_delta_note_on_event(150.0f64, 0, 64, 127),
-///        Event::new_delta_note_off_event(75.0f64, 0, 64),
+///        Event::new_delta_note_off_event(75.0f64, 0, 64, 0),
 ///        Event::new_delta_note_on_event(45.0f64, 0, 64, 127),
-///        Event::new_delta_note_off_event(120.0f64, 0, 64),
+///        Event::new_delta_note_off_event(120.0f64, 0, 64, 0),
 ///    ]
 ///)
 ///```
@@ -56,21 +92,87 @@ pub fn scale_event_ppq<
     })
 }
 
+/// Change the PPQ of an event sequence with integer deltas, without accumulating rounding errors.
+///
+/// Unlike [`scale_event_ppq`], the absolute time of each event is rescaled and rounded,
+/// and the new deltas are the differences between the rounded times. Events always end up
+/// on the closest tick (depending on the rounding) to their exact time, however long the sequence is.
+/// Sequences that are rescaled separately (e.g. the tracks of a file) stay aligned with each other.
+///
+/// ## Example
+///```
+///use midi_toolkit::{
+///    events::Event,
+///    pipe,
+///    sequence::{event::{rescale_ppq, Rounding}, to_vec_result, wrap_ok},
+///};
+///
+///let events = vec![
+///    Event::new_delta_note_on_event(100u64, 0, 64, 127),
+///    Event::new_delta_note_off_event(50, 0, 64, 0),
+///    Event::new_delta_note_on_event(30, 0, 64, 127),
+///    Event::new_delta_note_off_event(80, 0, 64, 0),
+///];
+///
+///let changed = pipe! {
+///    events.into_iter()
+///    |>wrap_ok()
+///    |>rescale_ppq(3, 2, Rounding::Floor)
+///    |>to_vec_result().unwrap()
+///};
+///
+///assert_eq!(
+///    changed,
+///    vec![
+///        Event::new_delta_note_on_event(66, 0, 64, 127),
+///        Event::new_delta_note_off_event(34, 0, 64, 0),
+///        Event::new_delta_note_on_event(20, 0, 64, 127),
+///        Event::new_delta_note_off_event(53, 0, 64, 0),
+///    ]
+///)
+///```
+pub fn rescale_ppq<E: MIDIDelta<u64>, Err, I: Iterator<Item = Result<E, Err>> + Sized>(
+    iter: I,
+    from: u16,
+    to: u16,
+    rounding: Rounding,
+) -> impl Iterator<Item = Result<E, Err>> {
+    assert!(from > 0, "Can't rescale from a ppq of 0");
+    GenIter(
+        #[coroutine]
+        move || {
+            let mut time: u128 = 0;
+            let mut new_time: u64 = 0;
+            for e in iter {
+                let mut e = unwrap!(e);
+                time += e.delta() as u128;
+                let rounded = rounding.divide(time * to as u128, from as u128) as u64;
+                e.set_delta(rounded - new_time);
+                new_time = rounded;
+                yield Ok(e);
+            }
+        },
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
         events::Event,
         pipe,
-        sequence::{event::scale_event_ppq, to_vec_result, wrap_ok},
+        sequence::{
+            event::{rescale_ppq, scale_event_ppq, Rounding},
+            to_vec_result, wrap_ok,
+        },
     };
 
     #[test]
     fn delta_change() {
         let events = vec![
             Event::new_delta_note_on_event(100.0f64, 0, 64, 127),
-            Event::new_delta_note_off_event(50.0f64, 0, 64),
+            Event::new_delta_note_off_event(50.0f64, 0, 64, 0),
             Event::new_delta_note_on_event(30.0f64, 0, 64, 127),
-            Event::new_delta_note_off_event(80.0f64, 0, 64),
+            Event::new_delta_note_off_event(80.0f64, 0, 64, 0),
         ];
 
         let changed = pipe! {
@@ -84,9 +186,9 @@ mod tests {
             changed,
             vec![
                 Event::new_delta_note_on_event(150.0f64, 0, 64, 127),
-                Event::new_delta_note_off_event(75.0f64, 0, 64),
+                Event::new_delta_note_off_event(75.0f64, 0, 64, 0),
                 Event::new_delta_note_on_event(45.0f64, 0, 64, 127),
-                Event::new_delta_note_off_event(120.0f64, 0, 64),
+                Event::new_delta_note_off_event(120.0f64, 0, 64, 0),
             ]
         )
     }
@@ -95,9 +197,9 @@ mod tests {
     fn delta_change_ints() {
         let events = vec![
             Event::new_delta_note_on_event(100, 0, 64, 127),
-            Event::new_delta_note_off_event(50, 0, 64),
+            Event::new_delta_note_off_event(50, 0, 64, 0),
             Event::new_delta_note_on_event(30, 0, 64, 127),
-            Event::new_delta_note_off_event(80, 0, 64),
+            Event::new_delta_note_off_event(80, 0, 64, 0),
         ];
 
         let changed = pipe! {
@@ -111,9 +213,9 @@ mod tests {
             changed,
             vec![
                 Event::new_delta_note_on_event(150, 0, 64, 127),
-                Event::new_delta_note_off_event(75, 0, 64),
+                Event::new_delta_note_off_event(75, 0, 64, 0),
                 Event::new_delta_note_on_event(45, 0, 64, 127),
-                Event::new_delta_note_off_event(120, 0, 64),
+                Event::new_delta_note_off_event(120, 0, 64, 0),
             ]
         )
     }
@@ -122,9 +224,9 @@ mod tests {
     fn delta_change_ints_divide() {
         let events = vec![
             Event::new_delta_note_on_event(100, 0, 64, 127),
-            Event::new_delta_note_off_event(50, 0, 64),
+            Event::new_delta_note_off_event(50, 0, 64, 0),
             Event::new_delta_note_on_event(30, 0, 64, 127),
-            Event::new_delta_note_off_event(80, 0, 64),
+            Event::new_delta_note_off_event(80, 0, 64, 0),
         ];
 
         let changed = pipe! {
@@ -138,10 +240,47 @@ mod tests {
             changed,
             vec![
                 Event::new_delta_note_on_event(66, 0, 64, 127),
-                Event::new_delta_note_off_event(33, 0, 64),
+                Event::new_delta_note_off_event(33, 0, 64, 0),
                 Event::new_delta_note_on_event(20, 0, 64, 127),
-                Event::new_delta_note_off_event(53, 0, 64),
+                Event::new_delta_note_off_event(53, 0, 64, 0),
             ]
         )
     }
+
+    #[test]
+    fn rescale_without_drift() {
+        let events = (0..1000000)
+            .map(|_| Event::new_delta_note_on_event(1u64, 0, 64, 127))
+            .collect::<Vec<_>>();
+
+        for (rounding, last_time) in [
+            (Rounding::Floor, 333333),
+            (Rounding::Nearest, 333333),
+            (Rounding::Ceil, 333334),
+        ] {
+            let changed = pipe! {
+                events.clone().into_iter()
+                |>wrap_ok()
+                |>rescale_ppq(960, 320, rounding)
+                |>to_vec_result().unwrap()
+            };
+            assert_eq!(changed.iter().map(|e| e.delta).sum::<u64>(), last_time);
+        }
+
+        let events = vec![
+            Event::new_delta_note_on_event(1u64, 0, 64, 127),
+            Event::new_delta_note_on_event(1, 0, 64, 127),
+            Event::new_delta_note_on_event(1, 0, 64, 127),
+        ];
+        let changed = pipe! {
+            events.into_iter()
+            |>wrap_ok()
+            |>rescale_ppq(2, 1, Rounding::Nearest)
+            |>to_vec_result().unwrap()
+        };
+        assert_eq!(
+            changed.iter().map(|e| e.delta).collect::<Vec<_>>(),
+            vec![1, 0, 1]
+        );
+    }
 }
@@ -9,9 +9,9 @@ use crate::{events::MIDIDelta, num::MIDINum};
 ///
 ///let events = vec![
 ///    Event::new_delta_note_on_event(100.0f64, 0, 64, 127),
-///    Event::new_delta_note_off_event(50.0f64, 0, 64),
+///    Event::new_delta_note_off_event(50.0f64, 0, 64, 0),
 ///    Event::new_delta_note_on_event(30.0f64, 0, 64, 127),
-///    Event::new_delta_note_off_event(80.0f64, 0, 64),
+///    Event::new_delta_note_off_event(80.0f64, 0, 64, 0),
 ///];
 ///
 ///let changed = pipe! {
@@ -25,9 +25,9 @@ use crate::{events::MIDIDelta, num::MIDINum};
 ///    changed,
 ///    vec![
 ///        Event::new_delta_note_on_event(150.0f64, 0, 64, 127),
-///        Event::new_delta_note_off_event(75.0f64, 0, 64),
+///        Event::new_delta_note_off_event(75.0f64, 0, 64, 0),
 ///        Event::new_delta_note_on_event(45.0f64, 0, 64, 127),
-///        Event::new_delta_note_off_event(120.0f64, 0, 64),
+///        Event::new_delta_note_off_event(120.0f64, 0, 64, 0),
 ///    ]
 ///)
 ///```
@@ -60,9 +60,9 @@ mod tests {
     fn time_change() {
         let events = vec![
             Event::new_delta_note_on_event(100.0f64, 0, 64, 127),
-            Event::new_delta_note_off_event(50.0f64, 0, 64),
+            Event::new_delta_note_off_event(50.0f64, 0, 64, 0),
             Event::new_delta_note_on_event(30.0f64, 0, 64, 127),
-            Event::new_delta_note_off_event(80.0f64, 0, 64),
+            Event::new_delta_note_off_event(80.0f64, 0, 64, 0),
         ];
 
         let changed = pipe! {
@@ -76,9 +76,9 @@ mod tests {
             changed,
             vec![
                 Event::new_delta_note_on_event(150.0f64, 0, 64, 127),
-                Event::new_delta_note_off_event(75.0f64, 0, 64),
+                Event::new_delta_note_off_event(75.0f64, 0, 64, 0),
                 Event::new_delta_note_on_event(45.0f64, 0, 64, 127),
-                Event::new_delta_note_off_event(120.0f64, 0, 64),
+                Event::new_delta_note_off_event(120.0f64, 0, 64, 0),
             ]
         )
     }
@@ -87,9 +87,9 @@ mod tests {
     fn time_change_ints() {
         let events = vec![
             Event::new_delta_note_on_event(100, 0, 64, 127),
-            Event::new_delta_note_off_event(50, 0, 64),
+            Event::new_delta_note_off_event(50, 0, 64, 0),
             Event::new_delta_note_on_event(30, 0, 64, 127),
-            Event::new_delta_note_off_event(80, 0, 64),
+            Event::new_delta_note_off_event(80, 0, 64, 0),
         ];
 
         let changed = pipe! {
@@ -104,9 +104,9 @@ mod tests {
             changed,
             vec![
                 Event::new_delta_note_on_event(200, 0, 64, 127),
-                Event::new_delta_note_off_event(100, 0, 64),
+                Event::new_delta_note_off_event(100, 0, 64, 0),
                 Event::new_delta_note_on_event(60, 0, 64, 127),
-                Event::new_delta_note_off_event(160, 0, 64),
+                Event::new_delta_note_off_event(160, 0, 64, 0),
             ]
         )
     }
@@ -0,0 +1,372 @@
+use std::collections::BTreeMap;
+
+use crate::gen_iter::GenIter;
+
+use crate::{
+    events::{Event, MIDIDelta},
+    num::MIDINum,
+    unwrap,
+};
+
+use super::{ChasedStates, StatefulEvent};
+
+/// Options for [`slice_events`].
+#[derive(Debug, Clone, Default)]
+pub struct SliceOptions {
+    /// Send note on events at the start of the slice for the notes that are held down at `start`.
+    /// Without this, only the notes that start inside the slice are played, and the note offs of
+    /// the notes that are held down at `start` are removed.
+    pub note_ons_at_start: bool,
+}
+
+/// The notes that were held down at the start of a slice without being played in it,
+/// counted per state key and per channel and key, so that their note offs can be removed.
+#[derive(Default)]
+struct ForgottenNotes {
+    counts: BTreeMap<u32, Vec<u32>>,
+    total: u32,
+}
+
+impl ForgottenNotes {
+    /// Returns true if the event is the note off of a forgotten note, which is then no longer counted.
+    fn take_note_off(&mut self, key: u32, event: &Event) -> bool {
+        let Event::NoteOff(e) = event else {
+            return false;
+        };
+        let Some(counts) = self.counts.get_mut(&key) else {
+            return false;
+        };
+        let count = &mut counts[(e.channel as usize & 0x0F) * 128 + (e.key as usize & 0x7F)];
+        if *count == 0 {
+            return false;
+        }
+        *count -= 1;
+        self.total -= 1;
+        true
+    }
+}
+
+impl ChasedStates {
+    fn release_held_notes<E: StatefulEvent>(&mut self) -> Vec<E> {
+        let mut events = Vec::new();
+        for (key, state) in self.states.iter_mut() {
+            let mut note_offs = Vec::new();
+            for (channel, channel_state) in state.channels.iter_mut().enumerate() {
+                for (note, velocities) in channel_state.held_notes.iter_mut().enumerate() {
+                    // Every overlapping note of the key needs its own note off
+                    for _ in velocities.drain(..) {
+                        note_offs.push(Event::new_note_off_event(channel as u8, note as u8, 0));
+                    }
+                }
+            }
+            events.extend(E::from_restore_events(note_offs, *key));
+        }
+        events
+    }
+
+    fn forget_held_notes(&mut self) -> ForgottenNotes {
+        let mut forgotten = ForgottenNotes::default();
+        for (key, state) in self.states.iter_mut() {
+            let mut counts = vec![0; 16 * 128];
+            for (channel, channel_state) in state.channels.iter_mut().enumerate() {
+                for (note, velocities) in channel_state.held_notes.iter_mut().enumerate() {
+                    counts[channel * 128 + note] = velocities.len() as u32;
+                    forgotten.total += velocities.len() as u32;
+                    velocities.clear();
+                }
+            }
+            forgotten.counts.insert(*key, counts);
+        }
+        forgotten
+    }
+}
+
+/// Cuts the events in `[start, end)` out of a sequence, rebasing their deltas to `start`.
+///
+/// The state before `start` is chased like in [`chase_state_from`](super::chase_state_from), and
+/// notes that are still held at `end` (or at the end of the sequence) get note off events at `end`.
+/// The iterator stops reading the input once it reaches `end`.
+///
+/// Works on any [`StatefulEvent`], so [`Track`](super::Track) events and batches can be sliced too.
+///
+/// ## Example
+///```
+///use midi_toolkit::{
+///    events::Event,
+///    pipe,
+///    sequence::{event::{slice_events, SliceOptions}, to_vec_result, wrap_ok},
+///};
+///
+///let events = vec![
+///    Event::new_delta_program_change_event(0u64, 0, 20),
+///    Event::new_delta_note_on_event(10, 0, 64, 127),
+///    Event::new_delta_note_on_event(50, 0, 65, 127),
+///    Event::new_delta_note_off_event(100, 0, 64, 0),
+///];
+///
+///let sliced = pipe!(
+///    events.into_iter()
+///    |>wrap_ok()
+///    |>slice_events(50, 100, SliceOptions { note_ons_at_start: true })
+///    |>to_vec_result().unwrap()
+///);
+///
+///assert_eq!(
+///    sliced,
+///    vec![
+///        Event::new_delta_program_change_event(0, 0, 20),
+///        Event::new_delta_note_on_event(0, 0, 64, 127),
+///        Event::new_delta_note_on_event(10, 0, 65, 127),
+///        Event::new_delta_note_off_event(40, 0, 64, 0),
+///        Event::new_delta_note_off_event(0, 0, 65, 0),
+///    ]
+///);
+///```
+pub fn slice_events<D, E, Err, I>(
+    iter: I,
+    start: D,
+    end: D,
+    options: SliceOptions,
+) -> impl Iterator<Item = Result<E, Err>>
+where
+    D: MIDINum,
+    E: StatefulEvent + MIDIDelta<D>,
+    I: Iterator<Item = Result<E, Err>> + Sized,
+{
+    GenIter(
+        #[coroutine]
+        move || {
+            let mut states = ChasedStates::default();
+            let mut forgotten = ForgottenNotes::default();
+            let mut time = D::zero();
+            let mut last_time = start;
+            let mut started = false;
+            let mut ended = false;
+
+            for e in iter {
+                let mut e = unwrap!(e);
+                time += e.delta();
+
+                if !started {
+                    if time < start {
+                        states.update(&e);
+                        continue;
+                    }
+
+                    for restore in states.restore_events::<E>(options.note_ons_at_start) {
+                        yield Ok(restore);
+                    }
+                    if !options.note_ons_at_start {
+                        forgotten = states.forget_held_notes();
+                    }
+                    started = true;
+                }
+
+                if time >= end {
+                    ended = true;
+                    break;
+                }
+
+                // The note offs of forgotten notes are removed, so that they don't end the notes of the slice
+                if forgotten.total > 0
+                    && !e.retain_events(&mut |key, event| !forgotten.take_note_off(key, event))
+                {
+                    continue;
+                }
+
+                states.update(&e);
+                e.set_delta(time - last_time);
+                last_time = time;
+                yield Ok(e);
+            }
+
+            if !started {
+                for restore in states.restore_events::<E>(options.note_ons_at_start) {
+                    yield Ok(restore);
+                }
+                if !options.note_ons_at_start {
+                    states.forget_held_notes();
+                }
+            }
+
+            // Notes are released at the end of the slice, or right after the last event if the sequence ends first
+            let release_time = if ended { end } else { last_time };
+            let mut note_offs = states.release_held_notes::<E>().into_iter();
+            if let Some(mut first) = note_offs.next() {
+                first.set_delta(release_time - last_time);
+                yield Ok(first);
+            }
+            for note_off in note_offs {
+                yield Ok(note_off);
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        events::Event,
+        pipe,
+        sequence::{
+            event::{
+                convert_events_into_batches, flatten_batches_to_events, into_track_events,
+                merge_events_array, slice_events, Delta, SliceOptions, Track,
+            },
+            to_vec_result, wrap_ok,
+        },
+    };
+
+    #[test]
+    fn slice_without_note_ons_at_start() {
+        let events = vec![
+            Event::new_delta_control_change_event(0u64, 0, 7, 80),
+            Event::new_delta_note_on_event(10, 0, 64, 127),
+            Event::new_delta_note_on_event(20, 0, 65, 127),
+            Event::new_delta_note_off_event(10, 0, 64, 0),
+            Event::new_delta_note_off_event(10, 0, 65, 0),
+            Event::new_delta_note_on_event(100, 0, 66, 127),
+        ];
+
+        let sliced = pipe!(
+            events.into_iter()
+            |>wrap_ok()
+            |>convert_events_into_batches()
+            |>slice_events(20, 1000, SliceOptions::default())
+            |>flatten_batches_to_events()
+            |>to_vec_result().unwrap()
+        );
+
+        // The sequence ends before the slice does, so the last note is released right after it
+        assert_eq!(
+            sliced,
+            vec![
+                Event::new_delta_control_change_event(0, 0, 7, 80),
+                Event::new_delta_note_on_event(10, 0, 65, 127),
+                Event::new_delta_note_off_event(20, 0, 65, 0),
+                Event::new_delta_note_on_event(100, 0, 66, 127),
+                Event::new_delta_note_off_event(0, 0, 66, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn slice_tracks() {
+        let track0 = vec![
+            Event::new_delta_tempo_event(0u64, 400000),
+            Event::new_delta_note_on_event(50, 0, 64, 127),
+            Event::new_delta_note_off_event(100, 0, 64, 0),
+        ];
+        let track1 = vec![
+            Event::new_delta_program_change_event(0u64, 1, 5),
+            Event::new_delta_note_on_event(40, 1, 60, 100),
+            Event::new_delta_note_off_event(40, 1, 60, 0),
+        ];
+
+        let tracks = vec![track0, track1]
+            .into_iter()
+            .enumerate()
+            .map(|(i, track)| into_track_events(track.into_iter().map(Ok::<_, ()>), i as u32))
+            .collect();
+
+        let sliced = pipe!(
+            tracks
+            |>merge_events_array()
+            |>slice_events(45, 100, SliceOptions { note_ons_at_start: true })
+            |>to_vec_result().unwrap()
+        );
+
+        let expected = [
+            Delta::new(0, Track::new(Event::new_tempo_event(400000), 0)),
+            Delta::new(0, Track::new(Event::new_program_change_event(1, 5), 1)),
+            Delta::new(0, Track::new(Event::new_note_on_event(1, 60, 100), 1)),
+            Delta::new(5, Track::new(Event::new_note_on_event(0, 64, 127), 0)),
+            Delta::new(30, Track::new(Event::new_note_off_event(1, 60, 0), 1)),
+            Delta::new(20, Track::new(Event::new_note_off_event(0, 64, 0), 0)),
+        ];
+        assert_eq!(sliced.len(), expected.len());
+        for (sliced, expected) in sliced.iter().zip(expected.iter()) {
+            assert_eq!(sliced.delta, expected.delta);
+            assert_eq!(sliced.track, expected.track);
+            assert_eq!(sliced.event.event, expected.event.event);
+        }
+    }
+
+    #[test]
+    fn slice_releases_stacked_notes() {
+        let events = vec![
+            Event::new_delta_note_on_event(0u64, 0, 64, 100),
+            Event::new_delta_note_on_event(10, 0, 64, 110),
+            Event::new_delta_note_on_event(10, 0, 64, 120),
+            Event::new_delta_note_off_event(100, 0, 64, 0),
+            Event::new_delta_note_off_event(0, 0, 64, 0),
+            Event::new_delta_note_off_event(0, 0, 64, 0),
+        ];
+
+        for note_ons_at_start in [false, true] {
+            let iter = events.clone().into_iter().map(Ok::<_, ()>);
+            let sliced = pipe!(
+                iter
+                |>slice_events(5, 50, SliceOptions { note_ons_at_start })
+                |>to_vec_result().unwrap()
+            );
+
+            let count = |on: bool| {
+                sliced
+                    .iter()
+                    .filter(|e| match &e.event {
+                        Event::NoteOn(_) => on,
+                        Event::NoteOff(_) => !on,
+                        _ => false,
+                    })
+                    .count()
+            };
+            assert_eq!(count(true), if note_ons_at_start { 3 } else { 2 });
+            assert_eq!(count(true), count(false));
+        }
+    }
+
+    #[test]
+    fn slice_removes_note_offs_of_forgotten_notes() {
+        let events = vec![
+            Event::new_delta_note_on_event(0u64, 0, 64, 100),
+            Event::new_delta_note_on_event(20, 0, 64, 110),
+            Event::new_delta_note_off_event(10, 0, 64, 0),
+            Event::new_delta_note_off_event(100, 0, 64, 0),
+        ];
+
+        let sliced = pipe!(
+            events.clone().into_iter()
+            |>wrap_ok()
+            |>slice_events(10, 50, SliceOptions::default())
+            |>to_vec_result().unwrap()
+        );
+        assert_eq!(
+            sliced,
+            vec![
+                Event::new_delta_note_on_event(10, 0, 64, 110),
+                Event::new_delta_note_off_event(30, 0, 64, 0),
+            ]
+        );
+
+        let tracks = pipe!(events.into_iter()|>wrap_ok()|>into_track_events(3));
+        let sliced = pipe!(
+            tracks
+            |>convert_events_into_batches()
+            |>slice_events(10, 50, SliceOptions::default())
+            |>flatten_batches_to_events()
+            |>to_vec_result().unwrap()
+        );
+        let sliced = sliced
+            .iter()
+            .map(|e| (e.delta, e.track, e.event.event.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sliced,
+            vec![
+                (10, 3, Event::new_note_on_event(0, 64, 110)),
+                (30, 3, Event::new_note_off_event(0, 64, 0)),
+            ]
+        );
+    }
+}
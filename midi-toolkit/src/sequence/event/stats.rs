@@ -4,12 +4,13 @@ use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterato
 
 use crate::{
     events::{Event, MIDIDelta, MIDIEventEnum, TempoEvent},
+    io::TimeDivision,
     num::MIDINum,
     pipe,
     sequence::{event::merge_events_array, to_vec, to_vec_result, wrap_ok},
 };
 
-use super::Delta;
+use super::{Delta, TempoMap};
 
 struct ElementCountDebug(&'static str, usize);
 
@@ -63,9 +64,15 @@ impl<T: MIDINum> ChannelStatistics<T> {
     }
 
     /// Calculate the length in seconds based on the tick length and the tempo events,
-    /// as well as the ppq
-    pub fn calculate_total_duration(&self, ppq: u16) -> Duration {
-        tempo_sequence_get_duration(&self.tempo_events, ppq, self.total_length_ticks)
+    /// as well as the ppq (or any other [`TimeDivision`])
+    pub fn calculate_total_duration(&self, division: impl Into<TimeDivision>) -> Duration {
+        tempo_sequence_get_duration(&self.tempo_events, division, self.total_length_ticks)
+    }
+
+    /// Build a [`TempoMap`] from the tempo events, for converting any tick into seconds
+    /// and back without walking over the tempo events each time.
+    pub fn tempo_map(&self, division: impl Into<TimeDivision>) -> TempoMap<T> {
+        TempoMap::from_tempo_events(&self.tempo_events, division)
     }
 }
 
@@ -118,14 +125,19 @@ impl<T: MIDINum> std::fmt::Debug for ChannelGroupStatistics<T> {
     }
 }
 
+/// Calculate the duration of `ticks` ticks, given the tempo events and the time division.
+///
+/// Tempo events are ignored for SMPTE divisions, as their tick length is fixed.
 pub fn tempo_sequence_get_duration<T: MIDINum>(
     tempos: &[Delta<T, TempoEvent>],
-    ppq: u16,
+    division: impl Into<TimeDivision>,
     ticks: T,
 ) -> Duration {
+    let division = division.into();
     let mut ticks = ticks;
     let mut time = 0.0;
-    let mut multiplier = (500000.0 / ppq as f64) / 1000000.0;
+    let mut multiplier = division.tick_seconds(500000);
+    let tempos = if division.is_smpte() { &[] } else { tempos };
     for t in tempos {
         let offset = t.delta();
         if offset > ticks {
@@ -135,7 +147,7 @@ pub fn tempo_sequence_get_duration<T: MIDINum>(
 
         let offset: f64 = offset.midi_num_into();
         time += multiplier * offset;
-        multiplier = (t.tempo as f64 / ppq as f64) / 1000000.0;
+        multiplier = division.tick_seconds(t.tempo);
     }
     let ticks: f64 = ticks.midi_num_into();
     time += multiplier * ticks;
@@ -235,3 +247,34 @@ pub fn get_channels_array_statistics<
 
     Ok(ChannelGroupStatistics { group, channels })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        events::TempoEvent,
+        io::TimeDivision,
+        sequence::event::{tempo_sequence_get_duration, Delta},
+    };
+
+    #[test]
+    fn duration_with_tempo() {
+        let tempos = vec![Delta::new(96u64, TempoEvent::new(250000))];
+
+        let duration = tempo_sequence_get_duration(&tempos, 96, 192u64);
+        assert_eq!(duration, Duration::from_millis(750));
+    }
+
+    #[test]
+    fn duration_smpte_ignores_tempo() {
+        let tempos = vec![Delta::new(96u64, TempoEvent::new(250000))];
+        let division = TimeDivision::Smpte {
+            fps: 25,
+            ticks_per_frame: 40,
+        };
+
+        let duration = tempo_sequence_get_duration(&tempos, division, 2000u64);
+        assert_eq!(duration, Duration::from_secs(2));
+    }
+}
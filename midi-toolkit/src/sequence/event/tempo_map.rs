@@ -0,0 +1,224 @@
+use std::time::Duration;
+
+use crate::{
+    events::{Event, MIDIDelta, MIDIEventEnum, TempoEvent},
+    io::TimeDivision,
+    num::MIDINum,
+};
+
+use super::Delta;
+
+/// The default tempo of a MIDI file (120 bpm), in microseconds per quarter note.
+const DEFAULT_TEMPO: u32 = 500000;
+
+/// A point where the tempo changes, with the time in seconds at which it happens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TempoChange<D: MIDINum> {
+    pub tick: D,
+    pub seconds: f64,
+    /// The new tempo, in microseconds per quarter note
+    pub tempo: u32,
+}
+
+/// The tempo changes of a sequence, for converting between ticks and seconds in O(log n).
+///
+/// For SMPTE divisions the tick length is fixed, so tempo changes are still recorded
+/// (for [`tempo_at`](TempoMap::tempo_at) and [`bpm_at`](TempoMap::bpm_at)) but don't affect the time.
+///
+/// ## Example
+///```
+///use midi_toolkit::{events::Event, sequence::event::TempoMap};
+///
+///let events = vec![
+///    Event::new_delta_tempo_event(0u64, 500000),
+///    Event::new_delta_note_on_event(96, 0, 64, 127),
+///    Event::new_delta_tempo_event(0, 250000),
+///];
+///let map = TempoMap::from_events(events.into_iter().map(Ok::<_, ()>), 96).unwrap();
+///
+///assert_eq!(map.ticks_to_seconds(96), 0.5);
+///assert_eq!(map.ticks_to_seconds(192), 0.75);
+///assert_eq!(map.seconds_to_ticks(0.75), 192.0);
+///assert_eq!(map.bpm_at(192), 240.0);
+///```
+#[derive(Debug, Clone, PartialEq)]
+pub struct TempoMap<D: MIDINum> {
+    division: TimeDivision,
+    /// Sorted by tick, always starts with the default tempo at tick 0
+    changes: Vec<TempoChange<D>>,
+}
+
+impl<D: MIDINum> TempoMap<D> {
+    /// Creates a map with only the default tempo of 120 bpm.
+    pub fn new(division: impl Into<TimeDivision>) -> Self {
+        TempoMap {
+            division: division.into(),
+            changes: vec![TempoChange {
+                tick: D::zero(),
+                seconds: 0.0,
+                tempo: DEFAULT_TEMPO,
+            }],
+        }
+    }
+
+    /// Builds a map from tempo changes at absolute ticks, which must be sorted.
+    /// If several changes happen at the same tick, the last one is used.
+    pub fn from_absolute_tempos(
+        tempos: impl IntoIterator<Item = (D, u32)>,
+        division: impl Into<TimeDivision>,
+    ) -> Self {
+        let mut map = TempoMap::new(division);
+        for (tick, tempo) in tempos {
+            map.push(tick, tempo);
+        }
+        map
+    }
+
+    /// Builds a map from tempo events with delta times, like the ones kept by [`ChannelStatistics`](super::ChannelStatistics).
+    pub fn from_tempo_events(
+        tempos: &[Delta<D, TempoEvent>],
+        division: impl Into<TimeDivision>,
+    ) -> Self {
+        let mut map = TempoMap::new(division);
+        let mut time = D::zero();
+        for tempo in tempos {
+            time += tempo.delta;
+            map.push(time, tempo.tempo);
+        }
+        map
+    }
+
+    /// Builds a map from the tempo events of a sequence, which should contain the tempo events of
+    /// every track (e.g. [`MIDIFile::iter_all_events_merged`](crate::io::MIDIFile::iter_all_events_merged)).
+    pub fn from_events<E: MIDIEventEnum + MIDIDelta<D>, Err>(
+        iter: impl Iterator<Item = Result<E, Err>>,
+        division: impl Into<TimeDivision>,
+    ) -> Result<Self, Err> {
+        let mut map = TempoMap::new(division);
+        let mut time = D::zero();
+        for e in iter {
+            let e = e?;
+            time += e.delta();
+            if let Event::Tempo(tempo) = e.as_event() {
+                map.push(time, tempo.tempo);
+            }
+        }
+        Ok(map)
+    }
+
+    fn push(&mut self, tick: D, tempo: u32) {
+        let last = self.changes.last_mut().unwrap();
+        if tick <= last.tick {
+            last.tempo = tempo;
+            return;
+        }
+
+        let ticks: f64 = (tick - last.tick).midi_num_into();
+        let seconds = last.seconds + ticks * self.division.tick_seconds(last.tempo);
+        self.changes.push(TempoChange {
+            tick,
+            seconds,
+            tempo,
+        });
+    }
+
+    pub fn division(&self) -> TimeDivision {
+        self.division
+    }
+
+    /// All the tempo changes, starting with the tempo at tick 0.
+    pub fn changes(&self) -> &[TempoChange<D>] {
+        &self.changes
+    }
+
+    fn change_at_tick(&self, tick: D) -> &TempoChange<D> {
+        let after = self.changes.partition_point(|c| c.tick <= tick);
+        &self.changes[after.saturating_sub(1)]
+    }
+
+    fn change_at_seconds(&self, seconds: f64) -> &TempoChange<D> {
+        let after = self.changes.partition_point(|c| c.seconds <= seconds);
+        &self.changes[after.saturating_sub(1)]
+    }
+
+    /// The tempo that is active at the tick, in microseconds per quarter note.
+    pub fn tempo_at(&self, tick: D) -> u32 {
+        self.change_at_tick(tick).tempo
+    }
+
+    /// The tempo that is active at the tick, in beats per minute.
+    pub fn bpm_at(&self, tick: D) -> f64 {
+        60000000.0 / self.tempo_at(tick) as f64
+    }
+
+    /// The time of a tick in seconds, from the start of the sequence.
+    pub fn ticks_to_seconds(&self, tick: D) -> f64 {
+        let change = self.change_at_tick(tick);
+        let ticks: f64 = (tick - change.tick).midi_num_into();
+        change.seconds + ticks * self.division.tick_seconds(change.tempo)
+    }
+
+    /// Same as [`ticks_to_seconds`](TempoMap::ticks_to_seconds), as a [`Duration`].
+    pub fn ticks_to_duration(&self, tick: D) -> Duration {
+        Duration::from_secs_f64(self.ticks_to_seconds(tick))
+    }
+
+    /// The tick at a time in seconds, from the start of the sequence.
+    ///
+    /// The result is fractional, round it to get the closest integer tick.
+    pub fn seconds_to_ticks(&self, seconds: f64) -> f64 {
+        let change = self.change_at_seconds(seconds);
+        let tick: f64 = change.tick.midi_num_into();
+        tick + (seconds - change.seconds) / self.division.tick_seconds(change.tempo)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        events::{Event, TempoEvent},
+        io::TimeDivision,
+        sequence::event::{Delta, TempoMap},
+    };
+
+    #[test]
+    fn tempo_map_queries() {
+        let tempos = vec![
+            Delta::new(100u64, TempoEvent::new(250000)),
+            Delta::new(0, TempoEvent::new(1000000)),
+            Delta::new(100, TempoEvent::new(500000)),
+        ];
+        let map = TempoMap::from_tempo_events(&tempos, 100);
+
+        // The second tempo event at tick 100 replaces the first one
+        assert_eq!(map.changes().len(), 3);
+        assert_eq!(map.tempo_at(99), 500000);
+        assert_eq!(map.tempo_at(100), 1000000);
+        assert_eq!(map.bpm_at(150), 60.0);
+
+        assert_eq!(map.ticks_to_seconds(100), 0.5);
+        assert_eq!(map.ticks_to_seconds(150), 1.0);
+        assert_eq!(map.ticks_to_seconds(300), 2.0);
+        for tick in [0u64, 37, 100, 150, 200, 1000] {
+            let seconds = map.ticks_to_seconds(tick);
+            assert!((map.seconds_to_ticks(seconds) - tick as f64).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn tempo_map_smpte() {
+        let events = vec![
+            Event::new_delta_tempo_event(0u64, 250000),
+            Event::new_delta_tempo_event(1000, 1000000),
+        ];
+        let division = TimeDivision::Smpte {
+            fps: 25,
+            ticks_per_frame: 40,
+        };
+        let map = TempoMap::from_events(events.into_iter().map(Ok::<_, ()>), division).unwrap();
+
+        assert_eq!(map.tempo_at(2000), 1000000);
+        assert_eq!(map.ticks_to_seconds(2000), 2.0);
+        assert_eq!(map.seconds_to_ticks(1.5), 1500.0);
+    }
+}
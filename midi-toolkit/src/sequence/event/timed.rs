@@ -0,0 +1,285 @@
+use std::{
+    cmp::Ordering,
+    io::Write,
+    ops::{Deref, DerefMut},
+};
+
+use crate::gen_iter::GenIter;
+
+use crate::{
+    events::{BatchTempo, MIDIDelta, MIDIEvent, MIDIEventEnum, SerializeEvent},
+    io::MIDIWriteError,
+    num::MIDINum,
+    unwrap,
+};
+
+use super::{Delta, Track};
+
+/// An event at an absolute time, as opposed to [`Delta`] which is relative to the previous event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Timed<D: MIDINum, E> {
+    pub time: D,
+    pub event: E,
+}
+
+impl<D: MIDINum, E> Timed<D, E> {
+    #[inline(always)]
+    pub fn new(time: D, event: E) -> Self {
+        Self { time, event }
+    }
+}
+
+impl<D: MIDINum, E> Deref for Timed<D, E> {
+    type Target = E;
+
+    fn deref(&self) -> &Self::Target {
+        &self.event
+    }
+}
+
+impl<D: MIDINum, E> DerefMut for Timed<D, E> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.event
+    }
+}
+
+impl<D: MIDINum, E: MIDIEventEnum> MIDIEvent for Timed<D, E> {
+    fn key(&self) -> Option<u8> {
+        self.event.key()
+    }
+
+    fn key_mut(&mut self) -> Option<&mut u8> {
+        self.event.key_mut()
+    }
+
+    fn channel(&self) -> Option<u8> {
+        self.event.channel()
+    }
+
+    fn channel_mut(&mut self) -> Option<&mut u8> {
+        self.event.channel_mut()
+    }
+
+    fn as_u32(&self) -> Option<u32> {
+        self.event.as_u32()
+    }
+}
+
+impl<D: MIDINum, E: MIDIEventEnum> MIDIEventEnum for Timed<D, E> {
+    #[inline(always)]
+    fn as_event(&self) -> &crate::events::Event {
+        self.event.as_event()
+    }
+
+    #[inline(always)]
+    fn as_event_mut(&mut self) -> &mut crate::events::Event {
+        self.event.as_event_mut()
+    }
+}
+
+impl<D: MIDINum, E: BatchTempo> BatchTempo for Timed<D, E> {
+    fn inner_tempo(&self) -> Option<u32> {
+        self.event.inner_tempo()
+    }
+
+    fn without_tempo(self) -> Option<Self> {
+        let time = self.time;
+        self.event
+            .without_tempo()
+            .map(|event| Self::new(time, event))
+    }
+}
+
+impl<D: MIDINum, E: SerializeEvent> SerializeEvent for Timed<D, E> {
+    fn serialize_event<T: Write>(&self, buf: &mut T) -> Result<usize, MIDIWriteError> {
+        self.event.serialize_event(buf)
+    }
+}
+
+/// Same as [`into_track_events`](super::into_track_events), for events with absolute times.
+pub fn into_track_timed_events<D: MIDINum, E, Err>(
+    iter: impl Iterator<Item = Result<Timed<D, E>, Err>>,
+    track: u32,
+) -> impl Iterator<Item = Result<Timed<D, Track<E>>, Err>> {
+    iter.map(move |e| e.map(|e| Timed::new(e.time, Track::new(e.event, track))))
+}
+
+/// Convert a sequence with delta times into a sequence with absolute times.
+///
+/// ## Example
+///```
+///use midi_toolkit::{
+///    events::Event,
+///    pipe,
+///    sequence::{event::{delta_to_absolute, Timed}, to_vec_result, wrap_ok},
+///};
+///
+///let events = vec![
+///    Event::new_delta_note_on_event(10u64, 0, 64, 127),
+///    Event::new_delta_note_off_event(20, 0, 64, 0),
+///];
+///
+///let timed = pipe! {
+///    events.into_iter()
+///    |>wrap_ok()
+///    |>delta_to_absolute()
+///    |>to_vec_result().unwrap()
+///};
+///
+///assert_eq!(
+///    timed,
+///    vec![
+///        Timed::new(10, Event::new_note_on_event(0, 64, 127)),
+///        Timed::new(30, Event::new_note_off_event(0, 64, 0)),
+///    ]
+///)
+///```
+pub fn delta_to_absolute<D: MIDINum, E, Err>(
+    iter: impl Iterator<Item = Result<Delta<D, E>, Err>>,
+) -> impl Iterator<Item = Result<Timed<D, E>, Err>> {
+    let mut time = D::zero();
+    iter.map(move |e| {
+        let e = e?;
+        time += e.delta();
+        Ok(Timed::new(time, e.event))
+    })
+}
+
+/// Convert a sequence with absolute times back into a sequence with delta times.
+///
+/// The times must be sorted, an event that is earlier than the previous one gets a delta of 0.
+/// Use [`sort_timed_events`] for unsorted events.
+pub fn absolute_to_delta<D: MIDINum, E, Err>(
+    iter: impl Iterator<Item = Result<Timed<D, E>, Err>>,
+) -> impl Iterator<Item = Result<Delta<D, E>, Err>> {
+    let mut time = D::zero();
+    iter.map(move |e| {
+        let e = e?;
+        let delta = if e.time > time {
+            e.time - time
+        } else {
+            D::zero()
+        };
+        time += delta;
+        Ok(Delta::new(delta, e.event))
+    })
+}
+
+/// Sort events with absolute times, in any order, into a sequence with delta times.
+///
+/// Events at the same time keep their order. The whole sequence is collected before the first event is returned.
+///
+/// ## Example
+///```
+///use midi_toolkit::{
+///    events::Event,
+///    pipe,
+///    sequence::{event::{sort_timed_events, Timed}, to_vec_result, wrap_ok},
+///};
+///
+///let events = vec![
+///    Timed::new(30u64, Event::new_note_off_event(0, 64, 0)),
+///    Timed::new(10, Event::new_note_on_event(0, 64, 127)),
+///];
+///
+///let sorted = pipe! {
+///    events.into_iter()
+///    |>wrap_ok()
+///    |>sort_timed_events()
+///    |>to_vec_result().unwrap()
+///};
+///
+///assert_eq!(
+///    sorted,
+///    vec![
+///        Event::new_delta_note_on_event(10, 0, 64, 127),
+///        Event::new_delta_note_off_event(20, 0, 64, 0),
+///    ]
+///)
+///```
+pub fn sort_timed_events<D: MIDINum, E, Err>(
+    iter: impl Iterator<Item = Result<Timed<D, E>, Err>>,
+) -> impl Iterator<Item = Result<Delta<D, E>, Err>> {
+    GenIter(
+        #[coroutine]
+        move || {
+            let mut events = Vec::new();
+            for e in iter {
+                events.push(unwrap!(e));
+            }
+            events.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(Ordering::Equal));
+
+            let mut time = D::zero();
+            for e in events {
+                let delta = e.time - time;
+                time = e.time;
+                yield Ok(Delta::new(delta, e.event));
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        events::Event,
+        pipe,
+        sequence::{
+            event::{
+                absolute_to_delta, delta_to_absolute, into_track_timed_events, sort_timed_events,
+                Timed,
+            },
+            to_vec_result, wrap_ok,
+        },
+    };
+
+    #[test]
+    fn absolute_round_trip() {
+        let events = vec![
+            Event::new_delta_note_on_event(10u64, 0, 64, 127),
+            Event::new_delta_note_on_event(0, 0, 65, 127),
+            Event::new_delta_note_off_event(20, 0, 64, 0),
+            Event::new_delta_note_off_event(5, 0, 65, 0),
+        ];
+
+        let round_trip = pipe! {
+            events.clone().into_iter()
+            |>wrap_ok()
+            |>delta_to_absolute()
+            |>absolute_to_delta()
+            |>to_vec_result().unwrap()
+        };
+        assert_eq!(round_trip, events);
+    }
+
+    #[test]
+    fn sort_stable_track_events() {
+        let events = vec![
+            Timed::new(5.5f64, Event::new_note_off_event(0, 64, 0)),
+            Timed::new(1.0, Event::new_note_on_event(0, 64, 127)),
+            Timed::new(5.5, Event::new_note_on_event(0, 64, 127)),
+            Timed::new(0.0, Event::new_tempo_event(400000)),
+        ];
+
+        let sorted = pipe! {
+            events.into_iter()
+            |>wrap_ok()
+            |>into_track_timed_events(2)
+            |>sort_timed_events()
+            |>to_vec_result().unwrap()
+        };
+
+        let expected = [
+            (0.0, Event::new_tempo_event(400000)),
+            (1.0, Event::new_note_on_event(0, 64, 127)),
+            (4.5, Event::new_note_off_event(0, 64, 0)),
+            (0.0, Event::new_note_on_event(0, 64, 127)),
+        ];
+        assert_eq!(sorted.len(), expected.len());
+        for (e, (delta, event)) in sorted.iter().zip(expected.iter()) {
+            assert_eq!(e.delta, *delta);
+            assert_eq!(e.track, 2);
+            assert_eq!(&e.event.event, event);
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use crate::gen_iter::GenIter;
+
+use crate::{events::BatchTempo, io::TimeDivision, num::MIDINum, unwrap};
+
+use super::Delta;
+
+fn convert_to_seconds<D, E, Err, I>(
+    iter: I,
+    division: TimeDivision,
+    keep_tempo: bool,
+) -> impl Iterator<Item = Result<Delta<f64, E>, Err>>
+where
+    D: MIDINum,
+    E: BatchTempo,
+    I: Iterator<Item = Result<Delta<D, E>, Err>> + Sized,
+{
+    GenIter(
+        #[coroutine]
+        move || {
+            // The time is counted from the last tempo change, so that rounding errors don't add up
+            let mut tempo_change_seconds = 0.0;
+            let mut ticks_since_tempo_change = D::zero();
+            let mut tick_seconds = division.tick_seconds(500000);
+            let mut last_seconds = 0.0;
+
+            for e in iter {
+                let e = unwrap!(e);
+                ticks_since_tempo_change += e.delta;
+                let ticks: f64 = ticks_since_tempo_change.midi_num_into();
+                let seconds = tempo_change_seconds + ticks * tick_seconds;
+
+                let mut event = e.event;
+                if let Some(tempo) = event.inner_tempo() {
+                    tempo_change_seconds = seconds;
+                    ticks_since_tempo_change = D::zero();
+                    tick_seconds = division.tick_seconds(tempo);
+                    if !keep_tempo {
+                        match event.without_tempo() {
+                            Some(without_tempo) => event = without_tempo,
+                            None => continue,
+                        }
+                    }
+                }
+
+                yield Ok(Delta::new(seconds - last_seconds, event));
+                last_seconds = seconds;
+            }
+        },
+    )
+}
+
+/// Convert the delta times of a sequence from ticks into seconds, using the tempo events in the sequence.
+/// The tempo events are removed, and their deltas are added to the next event.
+///
+/// The sequence must contain the tempo events of every track, e.g. the merged events of a file.
+/// Works on any event type with [`BatchTempo`], including [`EventBatch`](super::EventBatch) and [`Track`](super::Track) events.
+///
+/// ## Example
+///```
+///use midi_toolkit::{events::Event, pipe, sequence::{event::to_seconds, to_vec_result, wrap_ok}};
+///
+///let events = vec![
+///    Event::new_delta_note_on_event(96u64, 0, 64, 127),
+///    Event::new_delta_tempo_event(0, 250000),
+///    Event::new_delta_note_off_event(96, 0, 64, 0),
+///];
+///
+///let changed = pipe! {
+///    events.into_iter()
+///    |>wrap_ok()
+///    |>to_seconds(96)
+///    |>to_vec_result().unwrap()
+///};
+///
+///assert_eq!(
+///    changed,
+///    vec![
+///        Event::new_delta_note_on_event(0.5f64, 0, 64, 127),
+///        Event::new_delta_note_off_event(0.25f64, 0, 64, 0),
+///    ]
+///)
+///```
+pub fn to_seconds<D, E, Err, I>(
+    iter: I,
+    division: impl Into<TimeDivision>,
+) -> impl Iterator<Item = Result<Delta<f64, E>, Err>>
+where
+    D: MIDINum,
+    E: BatchTempo,
+    I: Iterator<Item = Result<Delta<D, E>, Err>> + Sized,
+{
+    convert_to_seconds(iter, division.into(), false)
+}
+
+/// Same as [`to_seconds`], except the tempo events are kept in the sequence,
+/// for example to write them back into a file later.
+pub fn to_seconds_with_tempo<D, E, Err, I>(
+    iter: I,
+    division: impl Into<TimeDivision>,
+) -> impl Iterator<Item = Result<Delta<f64, E>, Err>>
+where
+    D: MIDINum,
+    E: BatchTempo,
+    I: Iterator<Item = Result<Delta<D, E>, Err>> + Sized,
+{
+    convert_to_seconds(iter, division.into(), true)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        events::{Event, TempoEvent},
+        io::TimeDivision,
+        pipe,
+        sequence::{
+            event::{
+                convert_events_into_batches, flatten_track_batches_to_events, into_track_events,
+                tempo_sequence_get_duration, to_seconds, to_seconds_with_tempo, Delta,
+            },
+            to_vec_result,
+        },
+    };
+
+    fn make_events() -> Vec<Delta<u64, Event>> {
+        vec![
+            Event::new_delta_note_on_event(30, 0, 64, 127),
+            Event::new_delta_tempo_event(66, 250000),
+            Event::new_delta_note_off_event(0, 0, 64, 0),
+            Event::new_delta_tempo_event(100, 1000000),
+            Event::new_delta_tempo_event(0, 600000),
+            Event::new_delta_note_on_event(77, 0, 64, 127),
+            Event::new_delta_note_off_event(200, 0, 64, 0),
+        ]
+    }
+
+    #[test]
+    fn matches_tempo_sequence_duration() {
+        let events = make_events();
+        let total_ticks = events.iter().map(|e| e.delta).sum::<u64>();
+
+        let mut time = 0;
+        let mut tempos = Vec::new();
+        for e in events.iter() {
+            time += e.delta;
+            if let Event::Tempo(tempo) = &e.event {
+                tempos.push(Delta::new(time, TempoEvent::new(tempo.tempo)));
+                time = 0;
+            }
+        }
+
+        for division in [
+            TimeDivision::TicksPerQuarter(96),
+            TimeDivision::Smpte {
+                fps: 29,
+                ticks_per_frame: 80,
+            },
+        ] {
+            let expected = tempo_sequence_get_duration(&tempos, division, total_ticks);
+
+            let iter = events.clone().into_iter().map(Ok::<_, ()>);
+            let seconds = pipe!(iter|>to_seconds(division)|>to_vec_result().unwrap());
+            assert_eq!(seconds.len(), 4);
+            let total = seconds.iter().map(|e| e.delta).sum::<f64>();
+            assert!((total - expected.as_secs_f64()).abs() < 1e-9);
+
+            let iter = events.clone().into_iter().map(Ok::<_, ()>);
+            let with_tempo = pipe!(iter|>to_seconds_with_tempo(division)|>to_vec_result().unwrap());
+            assert_eq!(with_tempo.len(), events.len());
+            let total = with_tempo.iter().map(|e| e.delta).sum::<f64>();
+            assert!((total - expected.as_secs_f64()).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn track_batches_to_seconds() {
+        let iter = make_events().into_iter().map(Ok::<_, ()>);
+        let seconds = pipe!(
+            iter
+            |>convert_events_into_batches()
+            |>into_track_events(3)
+            |>to_seconds(96)
+            |>flatten_track_batches_to_events()
+            |>to_vec_result().unwrap()
+        );
+
+        let expected = [
+            (30.0 * 0.5 / 96.0, Event::new_note_on_event(0, 64, 127)),
+            (66.0 * 0.5 / 96.0, Event::new_note_off_event(0, 64, 0)),
+            (
+                100.0 * 0.25 / 96.0 + 77.0 * 0.6 / 96.0,
+                Event::new_note_on_event(0, 64, 127),
+            ),
+            (200.0 * 0.6 / 96.0, Event::new_note_off_event(0, 64, 0)),
+        ];
+        assert_eq!(seconds.len(), expected.len());
+        for (e, (delta, event)) in seconds.iter().zip(expected.iter()) {
+            assert!((e.delta - delta).abs() < 1e-9);
+            assert_eq!(e.track, 3);
+            assert_eq!(&e.event.event, event);
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{
     events::{BatchTempo, MIDIEvent, MIDIEventEnum, SerializeEvent},
     io::MIDIWriteError,
+    notes::MIDINote,
     num::MIDINum,
 };
 
@@ -87,9 +88,74 @@ impl<E: BatchTempo> BatchTempo for Track<E> {
     }
 }
 
+impl<T: MIDINum, N: MIDINote<T>> MIDINote<T> for Track<N> {
+    #[inline(always)]
+    fn start(&self) -> T {
+        self.event.start()
+    }
+
+    #[inline(always)]
+    fn start_mut(&mut self) -> &mut T {
+        self.event.start_mut()
+    }
+
+    #[inline(always)]
+    fn len(&self) -> T {
+        self.event.len()
+    }
+
+    #[inline(always)]
+    fn len_mut(&mut self) -> &mut T {
+        self.event.len_mut()
+    }
+
+    #[inline(always)]
+    fn key(&self) -> u8 {
+        self.event.key()
+    }
+
+    #[inline(always)]
+    fn key_mut(&mut self) -> &mut u8 {
+        self.event.key_mut()
+    }
+
+    #[inline(always)]
+    fn channel(&self) -> u8 {
+        self.event.channel()
+    }
+
+    #[inline(always)]
+    fn channel_mut(&mut self) -> &mut u8 {
+        self.event.channel_mut()
+    }
+
+    #[inline(always)]
+    fn velocity(&self) -> u8 {
+        self.event.velocity()
+    }
+
+    #[inline(always)]
+    fn velocity_mut(&mut self) -> &mut u8 {
+        self.event.velocity_mut()
+    }
+
+    #[inline(always)]
+    fn release_velocity(&self) -> u8 {
+        self.event.release_velocity()
+    }
+}
+
 pub fn into_track_events<D: MIDINum, E, Err>(
     iter: impl Iterator<Item = Result<Delta<D, E>, Err>>,
     track: u32,
 ) -> impl Iterator<Item = Result<Delta<D, Track<E>>, Err>> {
     iter.map(move |e| e.map(|e| Delta::new(e.delta, Track::new(e.event, track))))
 }
+
+/// Same as [`into_track_events`], for notes.
+pub fn into_track_notes<T: MIDINum, N: MIDINote<T>, Err>(
+    iter: impl Iterator<Item = Result<N, Err>>,
+    track: u32,
+) -> impl Iterator<Item = Result<Track<N>, Err>> {
+    iter.map(move |n| n.map(|n| Track::new(n, track)))
+}
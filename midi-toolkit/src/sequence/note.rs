@@ -2,3 +2,5 @@ mod merge_notes;
 pub use merge_notes::*;
 mod merge_notes_iter;
 pub use merge_notes_iter::*;
+mod remove_overlaps;
+pub use remove_overlaps::*;
@@ -0,0 +1,311 @@
+use std::collections::VecDeque;
+
+use crate::gen_iter::GenIter;
+
+use crate::{notes::MIDINote, num::MIDINum, unwrap};
+
+/// Which note is kept when two notes of the same key and channel overlap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverlapMode {
+    /// Keep the longer note, or the earlier one if they are the same length
+    #[default]
+    KeepLongest,
+    /// Keep the note with the higher velocity, or the earlier one if they are the same
+    KeepLoudest,
+    /// Keep both notes, and end the earlier note where the later one starts.
+    /// Notes that would end up with a length of 0 are removed.
+    Truncate,
+}
+
+impl OverlapMode {
+    /// Whether a kept note can still be removed or shortened by a note of the same key that starts at `time` or later.
+    fn can_change<T: MIDINum>(self, note: &impl MIDINote<T>, time: Option<T>) -> bool {
+        match time {
+            None => false,
+            Some(time) if note.end() <= time => false,
+            // Only a louder note can replace it
+            Some(_) if self == OverlapMode::KeepLoudest => note.velocity() < 0x7F,
+            Some(_) => true,
+        }
+    }
+}
+
+struct OverlapQueue<N> {
+    mode: OverlapMode,
+    /// The notes in the order of their start, removed notes are left as `None` until they reach the front
+    queue: VecDeque<Option<N>>,
+    /// The number of notes that have been popped from the front of the queue
+    popped: usize,
+    /// The position of the pending note of each key and channel, which is the last kept note of the key
+    /// and the only one that can still change. Every other note in the queue is final.
+    pending: Vec<Option<usize>>,
+}
+
+impl<N> OverlapQueue<N> {
+    fn new(mode: OverlapMode) -> Self {
+        Self {
+            mode,
+            queue: VecDeque::new(),
+            popped: 0,
+            pending: vec![None; 256 * 16],
+        }
+    }
+
+    #[inline(always)]
+    fn key_index(key: u8, channel: u8) -> usize {
+        key as usize * 16 + channel as usize
+    }
+
+    #[inline(always)]
+    fn held_mut(&mut self, index: usize) -> Option<&mut Option<N>> {
+        let position = self.pending[index]?;
+        self.queue.get_mut(position - self.popped)
+    }
+
+    #[inline(always)]
+    fn push(&mut self, index: usize, note: N) {
+        self.pending[index] = Some(self.popped + self.queue.len());
+        self.queue.push_back(Some(note));
+    }
+
+    /// Pops the next note if it can't be changed by notes that start at `time` or later.
+    #[inline(always)]
+    fn pop_done<T: MIDINum>(&mut self, time: Option<T>) -> Option<N>
+    where
+        N: MIDINote<T>,
+    {
+        loop {
+            if let Some(note) = self.queue.front()? {
+                let index = Self::key_index(note.key(), note.channel());
+                if self.pending[index] == Some(self.popped) {
+                    if self.mode.can_change(note, time) {
+                        return None;
+                    }
+                    self.pending[index] = None;
+                }
+            }
+
+            let note = self.queue.pop_front().unwrap();
+            self.popped += 1;
+            if let Some(note) = note {
+                return Some(note);
+            }
+        }
+    }
+}
+
+/// Remove the overlaps between notes of the same key and channel, e.g. the stacked duplicate notes of black MIDI.
+/// The notes must be sorted by their start, and the result is also sorted by start.
+///
+/// Only the last kept note of each key and channel is compared to the next note, so it is the only note of the key
+/// that can still change, and the state kept per key is bounded. A pending note is decided once the input reaches
+/// a note that starts at or after the pending note's end (or immediately for velocity 127 notes with
+/// [`OverlapMode::KeepLoudest`]). Every other note is yielded as soon as the notes that start before it have been yielded.
+///
+/// **NOTE:** The output buffer is not bounded. Because the result is sorted by start, the notes of every key that
+/// start while a pending note is held are buffered until that note is decided. In the worst case, one very long
+/// note holds back all the notes that start before its end.
+///
+/// ## Example
+///```
+///use midi_toolkit::{
+///    notes::Note,
+///    pipe,
+///    sequence::{note::{remove_overlaps, OverlapMode}, to_vec_result, wrap_ok},
+///};
+///
+///let note = |start, len, velocity| Note { start, len, key: 64, channel: 0, velocity, release_velocity: 0 };
+///let notes = vec![note(0u64, 10, 100), note(0, 20, 50), note(5, 5, 127), note(20, 10, 64)];
+///
+///let removed = pipe! {
+///    notes.into_iter()
+///    |>wrap_ok()
+///    |>remove_overlaps(OverlapMode::KeepLongest)
+///    |>to_vec_result().unwrap()
+///};
+///
+///assert_eq!(removed, vec![note(0, 20, 50), note(20, 10, 64)]);
+///```
+pub fn remove_overlaps<T: MIDINum, N: MIDINote<T>, Err>(
+    iter: impl Iterator<Item = Result<N, Err>> + Sized,
+    mode: OverlapMode,
+) -> impl Iterator<Item = Result<N, Err>> {
+    GenIter(
+        #[coroutine]
+        move || {
+            let mut queue = OverlapQueue::<N>::new(mode);
+
+            for note in iter {
+                let note = unwrap!(note);
+                let start = note.start();
+
+                while let Some(done) = queue.pop_done(Some(start)) {
+                    yield Ok(done);
+                }
+
+                let index = OverlapQueue::<N>::key_index(note.key(), note.channel());
+                let keep_new = match queue.held_mut(index) {
+                    Some(held) => match held {
+                        Some(held_note) if held_note.end() > start => match mode {
+                            OverlapMode::KeepLongest => {
+                                let keep_new = note.len() > held_note.len();
+                                if keep_new {
+                                    *held = None;
+                                }
+                                keep_new
+                            }
+                            OverlapMode::KeepLoudest => {
+                                let keep_new = note.velocity() > held_note.velocity();
+                                if keep_new {
+                                    *held = None;
+                                }
+                                keep_new
+                            }
+                            OverlapMode::Truncate => {
+                                if held_note.start() < start {
+                                    held_note.set_end(start);
+                                } else {
+                                    *held = None;
+                                }
+                                true
+                            }
+                        },
+                        _ => true,
+                    },
+                    None => true,
+                };
+
+                if keep_new {
+                    queue.push(index, note);
+                }
+            }
+
+            while let Some(done) = queue.pop_done(None) {
+                yield Ok(done);
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use crate::{
+        notes::Note,
+        pipe,
+        sequence::{
+            note::{remove_overlaps, OverlapMode},
+            to_vec_result, wrap_ok,
+        },
+    };
+
+    fn note(start: u64, len: u64, key: u8, velocity: u8) -> Note<u64> {
+        Note {
+            start,
+            len,
+            key,
+            channel: 0,
+            velocity,
+            release_velocity: 0,
+        }
+    }
+
+    #[test]
+    fn overlap_modes() {
+        let notes = vec![
+            note(0, 100, 60, 64),
+            note(0, 10, 64, 100),
+            note(0, 10, 64, 100),
+            note(5, 20, 64, 127),
+            note(10, 5, 65, 64),
+            note(20, 10, 64, 50),
+            note(40, 10, 64, 50),
+        ];
+
+        let cases = [
+            (
+                OverlapMode::KeepLongest,
+                vec![
+                    note(0, 100, 60, 64),
+                    note(5, 20, 64, 127),
+                    note(10, 5, 65, 64),
+                    note(40, 10, 64, 50),
+                ],
+            ),
+            (
+                OverlapMode::KeepLoudest,
+                vec![
+                    note(0, 100, 60, 64),
+                    note(5, 20, 64, 127),
+                    note(10, 5, 65, 64),
+                    note(40, 10, 64, 50),
+                ],
+            ),
+            (
+                OverlapMode::Truncate,
+                vec![
+                    note(0, 100, 60, 64),
+                    note(0, 5, 64, 100),
+                    note(5, 15, 64, 127),
+                    note(10, 5, 65, 64),
+                    note(20, 10, 64, 50),
+                    note(40, 10, 64, 50),
+                ],
+            ),
+        ];
+
+        for (mode, expected) in cases {
+            let removed = pipe! {
+                notes.clone().into_iter()
+                |>wrap_ok()
+                |>remove_overlaps(mode)
+                |>to_vec_result().unwrap()
+            };
+            assert_eq!(removed, expected, "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn final_notes_are_yielded_early() {
+        let mut notes = vec![note(0, 1000, 60, 127)];
+        for i in 1..100 {
+            notes.push(note(i * 10, 5, 61, 64));
+        }
+
+        for (mode, read_before_first) in [
+            (OverlapMode::KeepLoudest, 2),
+            (OverlapMode::KeepLongest, notes.len()),
+        ] {
+            let read = Cell::new(0);
+            let input = notes.iter().cloned().inspect(|_| read.set(read.get() + 1));
+            let mut removed = remove_overlaps(input.map(Ok::<_, ()>), mode);
+
+            assert_eq!(removed.next(), Some(Ok(note(0, 1000, 60, 127))));
+            assert_eq!(read.get(), read_before_first, "{mode:?}");
+            assert_eq!(removed.count(), 99);
+        }
+    }
+
+    #[test]
+    fn long_note_only_holds_back_later_notes() {
+        let mut notes = vec![note(0, 5, 61, 64), note(10, 5, 61, 64), note(20, 5, 61, 64)];
+        notes.push(note(25, 1000, 60, 64));
+        for i in 3..20 {
+            notes.push(note(i * 10, 5, 61, 64));
+        }
+
+        let read = Cell::new(0);
+        let input = notes.iter().cloned().inspect(|_| read.set(read.get() + 1));
+        let mut removed = remove_overlaps(input.map(Ok::<_, ()>), OverlapMode::KeepLongest);
+
+        // The notes on another key that start before the long note come out while it is still pending
+        assert_eq!(removed.by_ref().take(3).count(), 3);
+        assert_eq!(read.get(), 4);
+
+        // The notes that start after it wait until the input passes its end
+        assert_eq!(removed.next(), Some(Ok(note(25, 1000, 60, 64))));
+        assert_eq!(read.get(), notes.len());
+        assert_eq!(removed.count(), 17);
+    }
+}